@@ -5,9 +5,12 @@ use anyhow::Result;
 use rmcp::{ServiceExt, transport::stdio};  // MCPサーバー用のライブラリと標準入出力通信
 use tracing_subscriber::{self, EnvFilter};  // ログ出力機能用のライブラリ
 // use vectorium_common::get_embedding;
-// use vectorium_common::get_qdrant_client;
-// use qdrant_client::Qdrant;
-// use qdrant_client::qdrant::QueryPointsBuilder;
+use vectorium_common::{DEFAULT_MODEL, EmbeddingModel, get_model_embedding, get_qdrant_client};  // 埋め込みモデルとQdrant接続
+use qdrant_client::Qdrant;
+use qdrant_client::qdrant::{Condition, Filter, GetPointsBuilder, PointId, QueryPointsBuilder, ScoredPoint, Value};
+use qdrant_client::qdrant::point_id::PointIdOptions;
+use qdrant_client::qdrant::vectors_config;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 /// メインプログラムの開始点
 ///
@@ -100,6 +103,32 @@ pub struct ExamplePromptArgs {
 }
 
 
+/// 検索結果を前後に広げる行数の上限（問い合わせ回数が増えすぎないようにする）
+const MAX_EXPAND: u64 = 10;
+
+/// 検索ツールの引数
+///
+/// vector を指定すると、その名前付きベクトル（埋め込みモデル）で検索します。
+/// 省略した場合、無名ベクトルのコレクションでは既定モデルで検索します。
+/// 名前付きベクトルのコレクションでは既定モデルのベクトル（なければ唯一のベクトル）を使い、
+/// 選べない場合は使えるベクトル名を添えてエラーを返します。
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct SearchArgs {
+    /// 検索したい文章
+    pub query: String,
+    /// 検索に使う名前付きベクトル（例: "distiluse-base-multilingual-cased", "all-mini-lm-l12-v2"）
+    pub vector: Option<String>,
+    /// 返す件数（省略時は5件）
+    pub limit: Option<u64>,
+    /// true の場合、ヒットした行ではなく、その行を含むセクション全体を返す
+    /// （同じセクションの行が複数ヒットしても1回だけ返します）
+    pub parents: Option<bool>,
+    /// ヒットした行の前後それぞれ何行を加えて、ひと続きの文章として返すか
+    /// （省略時は0。parents を指定した場合は使いません）
+    pub expand: Option<u64>,
+}
+
+
 /// メインのカウンターサーバー構造体
 /// 
 /// この構造体が、MCPサーバー全体の中核となります。
@@ -122,7 +151,138 @@ pub struct Counter {
     /// クライアントからのプロンプト生成要求を適切な処理関数に振り分ける役割
     prompt_router: PromptRouter<Counter>,
 
-//    client: Qdrant,
+    /// 検索に使うQdrantクライアント
+    client: Qdrant,
+
+    /// テナント解決の設定
+    /// 呼び出し元のテナントを決められない場合、検索は拒否されます
+    tenants: TenantResolver,
+}
+
+
+/// 呼び出し元のテナントを決める設定
+///
+/// - 標準入出力で起動した場合: 環境変数 VECTORIUM_TENANT のテナント
+///   （サーバープロセスを起動した利用者＝1テナントとみなす）
+/// - HTTP経由の場合: Authorization ヘッダーの Bearer トークンを
+///   環境変数 VECTORIUM_TENANT_TOKENS（"トークン=テナント,..." 形式）で
+///   テナントに対応付けます。HTTPではトークンが必須で、VECTORIUM_TENANT は使いません。
+#[derive(Clone)]
+pub struct TenantResolver {
+    /// 標準入出力で使うテナント
+    stdio_tenant: Option<String>,
+    /// Bearerトークン -> テナントID
+    tokens: HashMap<String, String>,
+}
+
+impl TenantResolver {
+    /// 環境変数からテナント設定を読み込む
+    pub fn from_env() -> Self {
+        let stdio_tenant = std::env::var("VECTORIUM_TENANT")
+            .ok()
+            .map(|tenant| tenant.trim().to_string())
+            .filter(|tenant| !tenant.is_empty());
+
+        // "token=tenant" の組をカンマ区切りで並べた形式
+        let tokens = std::env::var("VECTORIUM_TENANT_TOKENS")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|pair| {
+                let (token, tenant) = pair.split_once('=')?;
+                let (token, tenant) = (token.trim(), tenant.trim());
+                (!token.is_empty() && !tenant.is_empty())
+                    .then(|| (token.to_string(), tenant.to_string()))
+            })
+            .collect();
+
+        Self { stdio_tenant, tokens }
+    }
+
+    /// リクエストの呼び出し元テナントを返す
+    ///
+    /// 決められない場合はエラー（テナント条件なしで検索させないため）
+    fn resolve(&self, context: &RequestContext<RoleServer>) -> Result<String, McpError> {
+        // HTTP経由の場合はBearerトークンから決める
+        if let Some(parts) = context.extensions.get::<axum::http::request::Parts>() {
+            let token = parts
+                .headers
+                .get(axum::http::header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .map(str::trim);
+            return token
+                .and_then(|token| self.tokens.get(token))
+                .cloned()
+                .ok_or_else(|| McpError::invalid_request("unknown_tenant", None));
+        }
+
+        self.stdio_tenant
+            .clone()
+            .ok_or_else(|| McpError::invalid_request("tenant_not_configured", None))
+    }
+}
+
+/// 前後の行で広げた検索結果の文章
+struct Passage {
+    /// ソース名（行番号はソース内で一意）
+    source: Option<String>,
+    /// 行番号 -> 本文
+    lines: BTreeMap<i64, String>,
+    /// まだ加えていない前後の行のポイントID（端に達したら None）
+    prev_id: Option<String>,
+    next_id: Option<String>,
+}
+
+impl Passage {
+    /// 検索でヒットした1行から始める
+    fn from_hit(point: &ScoredPoint) -> Self {
+        let payload = &point.payload;
+        // 行番号を持たないポイント（前後のつながりを記録する前に投入したもの）は広げない
+        let seq = payload_i64(payload, "seq");
+        Self {
+            source: payload_str(payload, "source"),
+            lines: BTreeMap::from([(seq.unwrap_or(0), payload_str(payload, "text").unwrap_or_default())]),
+            prev_id: seq.and_then(|_| payload_str(payload, "prev_id")),
+            next_id: seq.and_then(|_| payload_str(payload, "next_id")),
+        }
+    }
+
+    /// 取得した前後の行を加え、同じ向きで次にたどるIDを返す
+    fn extend(&mut self, payload: &HashMap<String, Value>, direction: &str) -> Option<String> {
+        let seq = payload_i64(payload, "seq")?;
+        self.lines.insert(seq, payload_str(payload, "text").unwrap_or_default());
+        payload_str(payload, direction)
+    }
+
+    fn overlaps(&self, other: &Passage) -> bool {
+        self.source.is_some()
+            && self.source == other.source
+            && other.lines.keys().any(|seq| self.lines.contains_key(seq))
+    }
+}
+
+/// ペイロードの整数フィールドを取り出す
+fn payload_i64(payload: &HashMap<String, Value>, key: &str) -> Option<i64> {
+    match payload.get(key).and_then(|v| v.kind.as_ref()) {
+        Some(qdrant_client::qdrant::value::Kind::IntegerValue(i)) => Some(*i),
+        _ => None,
+    }
+}
+
+/// ポイントIDを文字列にする（インジェスターはUUIDを使います）
+fn point_id_string(id: PointId) -> Option<String> {
+    match id.point_id_options? {
+        PointIdOptions::Uuid(id) => Some(id),
+        PointIdOptions::Num(id) => Some(id.to_string()),
+    }
+}
+
+/// ペイロードの文字列フィールドを取り出す（文字列以外や未設定の場合は None）
+fn payload_str(payload: &HashMap<String, Value>, key: &str) -> Option<String> {
+    match payload.get(key).and_then(|v| v.kind.as_ref()) {
+        Some(qdrant_client::qdrant::value::Kind::StringValue(s)) => Some(s.clone()),
+        _ => None,
+    }
 }
 
 // Counter構造体にツール機能を実装するための実装ブロック
@@ -149,7 +309,11 @@ impl Counter {
             // Self::prompt_router() はマクロによって自動生成される関数
             prompt_router: Self::prompt_router(),
 
-            // client: get_qdrant_client(),
+            // Qdrantクライアントを作成（接続は最初のリクエスト時）
+            client: get_qdrant_client(),
+
+            // テナント設定を環境変数から読み込む
+            tenants: TenantResolver::from_env(),
         }
     }
 
@@ -184,6 +348,236 @@ impl Counter {
         )]))
     }
 
+    /// ツール機能6: ナレッジベースの検索
+    ///
+    /// クエリを埋め込み、エイリアス "knowledge" が指すコレクションから
+    /// 類似した文章を返します。vector を指定すると、その名前付きベクトルを
+    /// 作ったモデルでクエリを埋め込み、同じベクトルに対して検索します。
+    ///
+    /// 検索は常に呼び出し元テナントの tenant_id に絞り込まれます。
+    /// この条件は引数では変更できず、テナントを決められない場合は検索しません。
+    #[tool(description = "ナレッジベースを検索します（vector で名前付きベクトルを選択可能）")]
+    async fn search(
+        &self,
+        Parameters(args): Parameters<SearchArgs>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        // 埋め込みより先にテナントを確認（未認証の呼び出しで計算しない）
+        let tenant = self.tenants.resolve(&context)?;
+
+        // 使うベクトルに対応したモデルでクエリを埋め込む
+        let model = match args.vector.as_deref() {
+            Some(name) => Some(EmbeddingModel::from_name(name).ok_or_else(|| {
+                McpError::invalid_params("unknown_vector", Some(json!({ "vector": name })))
+            })?),
+            // 省略時はコレクションのベクトル構成から決める
+            None => self.default_vector().await?,
+        };
+        let model_type = model.map_or(DEFAULT_MODEL, |model| model.model_type());
+        let embeddings = get_model_embedding(model_type, vec![args.query]).await;
+
+        let mut request = QueryPointsBuilder::new("knowledge")
+            .query(embeddings[0].clone())
+            .limit(args.limit.unwrap_or(5))
+            // 呼び出し元テナントのポイントだけを対象にする（必須条件）
+            .filter(Filter::must([Condition::matches("tenant_id", tenant.clone())]))
+            .with_payload(true);
+        // 名前付きベクトルの場合は検索対象のベクトル名を指定
+        if let Some(model) = model {
+            request = request.using(model.name());
+        }
+
+        let search_result = self.client
+            .query(request)
+            .await
+            .map_err(|e| McpError::internal_error(format!("Failed to query points: {}", e), None))?;
+
+        // ペイロードの text（親セクションを返す場合は parent_text）を取り出して結合
+        let expand = args.expand.unwrap_or(0).min(MAX_EXPAND);
+        let values = if args.parents.unwrap_or(false) {
+            self.parent_texts(&search_result.result, &tenant).await?
+        } else if expand > 0 {
+            self.expand_neighbors(&search_result.result, expand, &tenant).await?
+        } else {
+            search_result
+                .result
+                .iter()
+                .filter_map(|point| payload_str(&point.payload, "text"))
+                .collect::<Vec<String>>()
+        };
+
+        Ok(CallToolResult::success(vec![Content::text(values.join("\n\n"))]))
+    }
+
+    /// vector を省略した検索で使う名前付きベクトルを決めます
+    ///
+    /// 無名ベクトルのコレクションでは None（既定モデルで無名ベクトルを検索）を返します。
+    /// 名前付きベクトルのコレクションでは、既定モデルのベクトルがあればそれを、
+    /// ベクトルが1つだけならそれを使います。複数あって決められない場合は、
+    /// 使えるベクトル名を添えて invalid_params エラーを返します。
+    async fn default_vector(&self) -> Result<Option<EmbeddingModel>, McpError> {
+        let Some(names) = self.vector_names().await? else {
+            return Ok(None);
+        };
+
+        let default = EmbeddingModel::default().name();
+        let name = if names.iter().any(|name| name == default) {
+            default
+        } else if let [name] = names.as_slice() {
+            name.as_str()
+        } else {
+            return Err(McpError::invalid_params(
+                format!("vector is required; available vectors: {}", names.join(", ")),
+                Some(json!({ "vectors": names })),
+            ));
+        };
+        EmbeddingModel::from_name(name)
+            .map(Some)
+            .ok_or_else(|| McpError::internal_error(format!("Unknown vector in collection: {}", name), None))
+    }
+
+    /// エイリアス "knowledge" が指すコレクションの名前付きベクトル名（名前順）を返します
+    ///
+    /// 無名ベクトルのコレクションでは None を返します。
+    async fn vector_names(&self) -> Result<Option<Vec<String>>, McpError> {
+        // エイリアス導入前の実コレクションにも対応する
+        let aliases = self.client
+            .list_aliases()
+            .await
+            .map_err(|e| McpError::internal_error(format!("Failed to list aliases: {}", e), None))?;
+        let collection = aliases
+            .aliases
+            .into_iter()
+            .find(|alias| alias.alias_name == "knowledge")
+            .map_or_else(|| "knowledge".to_string(), |alias| alias.collection_name);
+
+        let info = self.client
+            .collection_info(collection.as_str())
+            .await
+            .map_err(|e| McpError::internal_error(format!("Failed to get collection info: {}", e), None))?;
+        let config = info
+            .result
+            .and_then(|info| info.config)
+            .and_then(|config| config.params)
+            .and_then(|params| params.vectors_config)
+            .and_then(|vectors| vectors.config);
+
+        Ok(match config {
+            Some(vectors_config::Config::ParamsMap(params)) => {
+                let mut names: Vec<String> = params.map.into_keys().collect();
+                names.sort();
+                Some(names)
+            }
+            _ => None,
+        })
+    }
+
+    /// ヒットした行の親セクションを取得します
+    ///
+    /// インジェスターで親子チャンクを有効にすると、各行のペイロードに parent_id
+    /// （セクションの本文 parent_text を持つポイントのID）が付きます。
+    /// ヒット順に親IDの重複を除き、親の本文をまとめて取得します。
+    /// 親を持たないポイント（親子チャンクを無効にして投入したもの）は、そのまま text を返します。
+    async fn parent_texts(&self, points: &[ScoredPoint], tenant: &str) -> Result<Vec<String>, McpError> {
+        // (親ID, 親が見つからない場合に返す本文) をヒット順に並べる
+        let mut seen = HashSet::new();
+        let mut entries: Vec<(Option<String>, String)> = Vec::new();
+        for point in points {
+            let text = payload_str(&point.payload, "text").unwrap_or_default();
+            match payload_str(&point.payload, "parent_id") {
+                Some(parent_id) => {
+                    if seen.insert(parent_id.clone()) {
+                        entries.push((Some(parent_id), text));
+                    }
+                }
+                None => entries.push((None, text)),
+            }
+        }
+
+        let ids: Vec<PointId> = seen.into_iter().map(PointId::from).collect();
+        let mut parents: HashMap<String, String> = HashMap::new();
+        if !ids.is_empty() {
+            let response = self.client
+                .get_points(GetPointsBuilder::new("knowledge", ids).with_payload(true))
+                .await
+                .map_err(|e| McpError::internal_error(format!("Failed to get parent points: {}", e), None))?;
+
+            for point in response.result {
+                // IDで取得するため検索時のフィルタが効かない。念のためテナントを確認する
+                if payload_str(&point.payload, "tenant_id").as_deref() != Some(tenant) {
+                    continue;
+                }
+                let Some(id) = point.id.and_then(point_id_string) else {
+                    continue;
+                };
+                if let Some(text) = payload_str(&point.payload, "parent_text") {
+                    parents.insert(id, text);
+                }
+            }
+        }
+
+        Ok(entries
+            .into_iter()
+            .map(|(parent_id, text)| parent_id.and_then(|id| parents.remove(&id)).unwrap_or(text))
+            .collect())
+    }
+
+    /// ヒットした行の前後の行を加え、ひと続きの文章にします
+    ///
+    /// インジェスターは各行のペイロードに、同じソースで前後に投入した行の
+    /// ポイントID（prev_id / next_id）と行番号（seq）を記録しています。
+    /// 前後のIDをたどって1段ずつまとめて取得するため、問い合わせは最大 expand 回です。
+    /// 同じソースで範囲が重なるヒットは、1つの文章にまとめて返します。
+    async fn expand_neighbors(&self, points: &[ScoredPoint], expand: u64, tenant: &str) -> Result<Vec<String>, McpError> {
+        let mut passages: Vec<Passage> = points.iter().map(Passage::from_hit).collect();
+
+        for _ in 0..expand {
+            let ids: HashSet<String> = passages
+                .iter()
+                .flat_map(|passage| [passage.prev_id.clone(), passage.next_id.clone()])
+                .flatten()
+                .collect();
+            if ids.is_empty() {
+                break;
+            }
+
+            let response = self.client
+                .get_points(
+                    GetPointsBuilder::new("knowledge", ids.into_iter().map(PointId::from).collect::<Vec<_>>())
+                        .with_payload(true),
+                )
+                .await
+                .map_err(|e| McpError::internal_error(format!("Failed to get neighbor points: {}", e), None))?;
+
+            // IDで取得するため検索時のフィルタが効かない。念のためテナントを確認する
+            let neighbors: HashMap<String, HashMap<String, Value>> = response
+                .result
+                .into_iter()
+                .filter(|point| payload_str(&point.payload, "tenant_id").as_deref() == Some(tenant))
+                .filter_map(|point| Some((point_id_string(point.id?)?, point.payload)))
+                .collect();
+
+            for passage in &mut passages {
+                passage.prev_id = passage.prev_id.take().and_then(|id| passage.extend(neighbors.get(&id)?, "prev_id"));
+                passage.next_id = passage.next_id.take().and_then(|id| passage.extend(neighbors.get(&id)?, "next_id"));
+            }
+        }
+
+        // 同じソースで行番号の範囲が重なる文章をまとめる（ヒット順を保つ）
+        let mut merged: Vec<Passage> = Vec::new();
+        for passage in passages {
+            match merged.iter_mut().find(|other| other.overlaps(&passage)) {
+                Some(other) => other.lines.extend(passage.lines),
+                None => merged.push(passage),
+            }
+        }
+
+        Ok(merged
+            .into_iter()
+            .map(|passage| passage.lines.into_values().collect::<Vec<_>>().join("\n"))
+            .collect())
+    }
+
     // #[tool(description = "DBからデータを取得します")]
     // async fn fetch_data(&self, Parameters(object): Parameters<JsonObject>) -> Result<CallToolResult, McpError> {
     //     let query_key = serde_json::Value::Object(object).to_string();
@@ -273,7 +667,7 @@ impl ServerHandler for Counter {
             
             // クライアント向けの使用説明書
             instructions: Some(
-                "このサーバーはカウンター操作とプロンプト応答機能を提供します。\n\n利用可能なツール:\n- increment: カウンターを1増やす\n- decrement: カウンターを1減らす\n- get_value: 現在のカウンター値を取得\n- say_hello: 挨拶メッセージを返す\n- echo: 送信されたデータをそのまま返す\n- sum: 2つの数値の合計を計算\n\n利用可能なプロンプト:\n- example_prompt: 例示用のプロンプト生成\n- counter_analysis: カウンター分析用のプロンプト生成".to_string()
+                "このサーバーはカウンター操作とプロンプト応答機能を提供します。\n\n利用可能なツール:\n- increment: カウンターを1増やす\n- decrement: カウンターを1減らす\n- get_value: 現在のカウンター値を取得\n- say_hello: 挨拶メッセージを返す\n- echo: 送信されたデータをそのまま返す\n- sum: 2つの数値の合計を計算\n- search: ナレッジベースを検索（vector で名前付きベクトルを選択、呼び出し元テナントのデータのみ）\n\n利用可能なプロンプト:\n- example_prompt: 例示用のプロンプト生成\n- counter_analysis: カウンター分析用のプロンプト生成".to_string()
             ),
         }
     }
@@ -0,0 +1,165 @@
+use anyhow::{Context, Result, anyhow};
+use rust_bert::pipelines::sentence_embeddings::{
+    SentenceEmbeddingsBuilder, SentenceEmbeddingsModelType,
+};
+use serde::Deserialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use tokio::sync::oneshot;
+
+// 埋め込みワーカープールの設定
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EmbeddingPoolConfig {
+    // モデルインスタンス数（0 の場合はCPUコア数から自動決定）
+    pub instances: usize,
+    // インスタンスごとの演算スレッド数
+    pub intra_op_threads: usize,
+    // 1回の encode に渡す最大文数
+    pub batch_size: usize,
+}
+
+impl Default for EmbeddingPoolConfig {
+    fn default() -> Self {
+        Self {
+            instances: 0,
+            intra_op_threads: 2,
+            batch_size: 128,
+        }
+    }
+}
+
+impl EmbeddingPoolConfig {
+    // 実際に起動するインスタンス数
+    pub fn resolved_instances(&self) -> usize {
+        if self.instances > 0 {
+            return self.instances;
+        }
+
+        let cores = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        (cores / self.intra_op_threads.max(1)).max(1)
+    }
+}
+
+// ワーカーへの依頼
+struct Job {
+    texts: Vec<String>,
+    reply: oneshot::Sender<Result<Vec<Vec<f32>>>>,
+}
+
+// モデルインスタンスを複数保持する埋め込みワーカープール
+pub struct EmbeddingPool {
+    senders: Vec<mpsc::Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+    next: AtomicUsize,
+    batch_size: usize,
+}
+
+impl EmbeddingPool {
+    // ワーカースレッドを起動し、全インスタンスのモデル読み込み完了を待つ
+    pub fn new(
+        model_type: SentenceEmbeddingsModelType,
+        config: &EmbeddingPoolConfig,
+    ) -> Result<Self> {
+        let instances = config.resolved_instances();
+        let intra_op_threads = config.intra_op_threads.max(1) as i32;
+        let (ready_tx, ready_rx) = mpsc::channel::<Result<()>>();
+
+        let mut senders = Vec::with_capacity(instances);
+        let mut workers = Vec::with_capacity(instances);
+
+        for index in 0..instances {
+            let (job_tx, job_rx) = mpsc::channel::<Job>();
+            let ready_tx = ready_tx.clone();
+
+            let handle = std::thread::Builder::new()
+                .name(format!("embedding-worker-{}", index))
+                .spawn(move || {
+                    // OpenMPのスレッド数は呼び出しスレッドごとに保持される
+                    tch::set_num_threads(intra_op_threads);
+
+                    let model = match SentenceEmbeddingsBuilder::remote(model_type).create_model() {
+                        Ok(model) => {
+                            let _ = ready_tx.send(Ok(()));
+                            model
+                        }
+                        Err(e) => {
+                            let _ = ready_tx
+                                .send(Err(anyhow!(e).context("Failed to create embeddings model")));
+                            return;
+                        }
+                    };
+
+                    for job in job_rx {
+                        let result = model
+                            .encode(&job.texts)
+                            .map_err(|e| anyhow!(e).context("Failed to encode sentences"));
+                        let _ = job.reply.send(result);
+                    }
+                })
+                .context("Failed to spawn embedding worker")?;
+
+            senders.push(job_tx);
+            workers.push(handle);
+        }
+        drop(ready_tx);
+
+        for _ in 0..instances {
+            ready_rx
+                .recv()
+                .context("Embedding worker exited during startup")??;
+        }
+
+        Ok(Self {
+            senders,
+            workers,
+            next: AtomicUsize::new(0),
+            batch_size: config.batch_size.max(1),
+        })
+    }
+
+    pub fn instances(&self) -> usize {
+        self.senders.len()
+    }
+
+    // 文をバッチに分割してラウンドロビンで投入し、入力順に結果を結合
+    pub async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        let total = texts.len();
+        let mut pending = Vec::with_capacity(total.div_ceil(self.batch_size));
+        let mut texts = texts.into_iter().peekable();
+
+        while texts.peek().is_some() {
+            let batch = texts.by_ref().take(self.batch_size).collect();
+            let (reply, receiver) = oneshot::channel();
+            let index = self.next.fetch_add(1, Ordering::Relaxed) % self.senders.len();
+
+            self.senders[index]
+                .send(Job {
+                    texts: batch,
+                    reply,
+                })
+                .map_err(|_| anyhow!("Embedding worker {} is not running", index))?;
+            pending.push(receiver);
+        }
+
+        let mut embeddings = Vec::with_capacity(total);
+        for receiver in pending {
+            embeddings.extend(receiver.await.context("Embedding worker dropped a job")??);
+        }
+
+        Ok(embeddings)
+    }
+}
+
+impl Drop for EmbeddingPool {
+    fn drop(&mut self) {
+        // 送信側を閉じるとワーカーのループが終了する
+        self.senders.clear();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
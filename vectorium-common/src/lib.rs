@@ -3,27 +3,40 @@ use rust_bert::pipelines::sentence_embeddings::{
     SentenceEmbeddingsBuilder, SentenceEmbeddingsModelType,
 };
 
+mod embedding_pool;
+mod models;
+
+pub use embedding_pool::{EmbeddingPool, EmbeddingPoolConfig};
+pub use models::EmbeddingModel;
+
+// インジェストと検索で共通して使用する埋め込みモデル
+pub const DEFAULT_MODEL: SentenceEmbeddingsModelType =
+    SentenceEmbeddingsModelType::DistiluseBaseMultilingualCased;
+
 pub async fn get_embedding(texts: Vec<String>) -> Vec<Vec<f32>> {
-    let embeddings = tokio::task::spawn_blocking(move || {
-        let sentence_embeddings_model = SentenceEmbeddingsBuilder::remote(
-            SentenceEmbeddingsModelType::DistiluseBaseMultilingualCased,
-        )
-        .create_model()
-        .expect("Failed to create embeddings model");
+    get_model_embedding(DEFAULT_MODEL, texts).await
+}
+
+// 指定したモデルで埋め込む（名前付きベクトルの検索用）
+pub async fn get_model_embedding(
+    model_type: SentenceEmbeddingsModelType,
+    texts: Vec<String>,
+) -> Vec<Vec<f32>> {
+    tokio::task::spawn_blocking(move || {
+        let sentence_embeddings_model = SentenceEmbeddingsBuilder::remote(model_type)
+            .create_model()
+            .expect("Failed to create embeddings model");
 
         sentence_embeddings_model
             .encode(&texts)
             .expect("Failed to encode sentences")
     })
     .await
-    .expect("Failed to join blocking task");
-
-    embeddings
+    .expect("Failed to join blocking task")
 }
 
 pub fn get_qdrant_client() -> Qdrant {
-    let client = Qdrant::from_url("http://localhost:6334")
+    Qdrant::from_url("http://localhost:6334")
         .build()
-        .expect("Failed to build client");
-    client
+        .expect("Failed to build client")
 }
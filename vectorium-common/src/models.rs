@@ -0,0 +1,75 @@
+use rust_bert::pipelines::sentence_embeddings::SentenceEmbeddingsModelType;
+use serde::Deserialize;
+
+// 名前付きベクトルとして格納できる埋め込みモデル（設定ファイルではケバブケースで指定）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmbeddingModel {
+    #[default]
+    DistiluseBaseMultilingualCased,
+    BertBaseNliMeanTokens,
+    AllMiniLmL12V2,
+    AllMiniLmL6V2,
+    AllDistilrobertaV1,
+    ParaphraseAlbertSmallV2,
+    SentenceT5Base,
+}
+
+impl EmbeddingModel {
+    pub const ALL: [EmbeddingModel; 7] = [
+        EmbeddingModel::DistiluseBaseMultilingualCased,
+        EmbeddingModel::BertBaseNliMeanTokens,
+        EmbeddingModel::AllMiniLmL12V2,
+        EmbeddingModel::AllMiniLmL6V2,
+        EmbeddingModel::AllDistilrobertaV1,
+        EmbeddingModel::ParaphraseAlbertSmallV2,
+        EmbeddingModel::SentenceT5Base,
+    ];
+
+    // コレクション内のベクトル名（設定ファイルでの名前と同じ）
+    pub fn name(self) -> &'static str {
+        match self {
+            EmbeddingModel::DistiluseBaseMultilingualCased => "distiluse-base-multilingual-cased",
+            EmbeddingModel::BertBaseNliMeanTokens => "bert-base-nli-mean-tokens",
+            EmbeddingModel::AllMiniLmL12V2 => "all-mini-lm-l12-v2",
+            EmbeddingModel::AllMiniLmL6V2 => "all-mini-lm-l6-v2",
+            EmbeddingModel::AllDistilrobertaV1 => "all-distilroberta-v1",
+            EmbeddingModel::ParaphraseAlbertSmallV2 => "paraphrase-albert-small-v2",
+            EmbeddingModel::SentenceT5Base => "sentence-t5-base",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|model| model.name() == name)
+    }
+
+    pub fn model_type(self) -> SentenceEmbeddingsModelType {
+        match self {
+            EmbeddingModel::DistiluseBaseMultilingualCased => {
+                SentenceEmbeddingsModelType::DistiluseBaseMultilingualCased
+            }
+            EmbeddingModel::BertBaseNliMeanTokens => {
+                SentenceEmbeddingsModelType::BertBaseNliMeanTokens
+            }
+            EmbeddingModel::AllMiniLmL12V2 => SentenceEmbeddingsModelType::AllMiniLmL12V2,
+            EmbeddingModel::AllMiniLmL6V2 => SentenceEmbeddingsModelType::AllMiniLmL6V2,
+            EmbeddingModel::AllDistilrobertaV1 => SentenceEmbeddingsModelType::AllDistilrobertaV1,
+            EmbeddingModel::ParaphraseAlbertSmallV2 => {
+                SentenceEmbeddingsModelType::ParaphraseAlbertSmallV2
+            }
+            EmbeddingModel::SentenceT5Base => SentenceEmbeddingsModelType::SentenceT5Base,
+        }
+    }
+
+    // 出力ベクトルの次元数
+    pub fn dimension(self) -> u64 {
+        match self {
+            EmbeddingModel::DistiluseBaseMultilingualCased => 512,
+            EmbeddingModel::AllMiniLmL12V2 | EmbeddingModel::AllMiniLmL6V2 => 384,
+            EmbeddingModel::BertBaseNliMeanTokens
+            | EmbeddingModel::AllDistilrobertaV1
+            | EmbeddingModel::ParaphraseAlbertSmallV2
+            | EmbeddingModel::SentenceT5Base => 768,
+        }
+    }
+}
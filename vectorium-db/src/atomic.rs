@@ -0,0 +1,42 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+// 一時ファイルに書いてからリネームし、途中で落ちても壊れないようにする（what はエラー表示用の名前）
+pub fn write(path: &Path, content: &[u8], what: &str) -> Result<()> {
+    let tmp_path =
+        tmp_path(path).with_context(|| format!("Invalid {} path: {}", what, path.display()))?;
+    std::fs::write(&tmp_path, content)
+        .with_context(|| format!("Failed to write {}: {}", what, tmp_path.display()))?;
+    std::fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to replace {}: {}", what, path.display()))?;
+    Ok(())
+}
+
+// 同じディレクトリの「ファイル名.tmp」（拡張子だけ違うファイル同士で一時ファイルが衝突しない）
+fn tmp_path(path: &Path) -> Option<PathBuf> {
+    let mut file_name = path.file_name()?.to_os_string();
+    file_name.push(".tmp");
+    Some(path.with_file_name(file_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tmp_path_keeps_the_full_file_name() {
+        assert_eq!(
+            tmp_path(Path::new("state/report.json")),
+            Some(PathBuf::from("state/report.json.tmp"))
+        );
+        assert_ne!(
+            tmp_path(Path::new("state/ingest.json")),
+            tmp_path(Path::new("state/ingest.yaml"))
+        );
+        assert_eq!(
+            tmp_path(Path::new("checkpoint")),
+            Some(PathBuf::from("checkpoint.tmp"))
+        );
+        assert_eq!(tmp_path(Path::new("/")), None);
+    }
+}
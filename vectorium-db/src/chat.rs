@@ -0,0 +1,360 @@
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use qdrant_client::qdrant::Value;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::text::{Passage, TextIngester};
+
+// チャットログ読み込みの設定
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ChatConfig {
+    // 前のメッセージからこの秒数以上空いたら別の会話とみなす
+    pub window_gap_secs: i64,
+    // 1つの会話に含める最大メッセージ数（埋め込みモデルの入力長に収めるため）
+    pub max_window_messages: usize,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            window_gap_secs: 600,
+            max_window_messages: 30,
+        }
+    }
+}
+
+// エクスポートされたメッセージ（Slack形式の ts やユーザー名の別名も受け付ける）
+#[derive(Debug, Deserialize)]
+struct ChatMessage {
+    #[serde(default, alias = "channel_name")]
+    channel: Option<String>,
+    #[serde(default, alias = "user_name", alias = "author", alias = "sender")]
+    user: Option<String>,
+    #[serde(default)]
+    text: String,
+    #[serde(alias = "ts", alias = "time", alias = "date")]
+    timestamp: Timestamp,
+}
+
+// RFC 3339 またはUNIX時刻（秒、文字列でも可）
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Timestamp {
+    Seconds(f64),
+    Text(String),
+}
+
+impl Timestamp {
+    fn parse(&self) -> Result<DateTime<Utc>> {
+        let seconds = match self {
+            Timestamp::Seconds(seconds) => *seconds,
+            Timestamp::Text(text) => match text.parse::<f64>() {
+                Ok(seconds) => seconds,
+                Err(_) => {
+                    return DateTime::parse_from_rfc3339(text)
+                        .map(|time| time.with_timezone(&Utc))
+                        .with_context(|| format!("Invalid timestamp: {}", text));
+                }
+            },
+        };
+        DateTime::from_timestamp_millis((seconds * 1000.0) as i64)
+            .with_context(|| format!("Timestamp out of range: {}", seconds))
+    }
+}
+
+// メッセージの配列、または messages に配列を持つオブジェクト
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ChatExport {
+    Messages(Vec<ChatMessage>),
+    Wrapped { messages: Vec<ChatMessage> },
+}
+
+// 会話ウィンドウ用に整えたメッセージ
+struct Entry {
+    time: DateTime<Utc>,
+    user: String,
+    text: String,
+}
+
+// 投入結果
+#[derive(Debug, Default)]
+pub struct ChatIngested {
+    pub files: u64,
+    pub messages: u64,
+    pub channels: u64,
+    pub windows: u64,
+    pub points: u64,
+}
+
+// JSONファイル（ディレクトリの場合は配下の *.json すべて）
+fn collect_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let pattern = path.join("**").join("*.json");
+    let mut files = glob::glob(&pattern.to_string_lossy())
+        .context("Failed to read glob pattern")?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .context("Failed to collect chat files")?;
+    files.sort();
+    Ok(files)
+}
+
+// channel を持たないメッセージのチャンネル名（Slackのエクスポートはチャンネルごとのディレクトリ）
+fn default_channel(root: &Path, file: &Path) -> String {
+    let directory = file.parent().filter(|parent| *parent != root);
+    directory
+        .and_then(|parent| parent.file_name())
+        .or_else(|| file.file_stem())
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+// 同じミリ秒に始まるウィンドウを区別するため、開始時刻（ミリ秒）に掛ける数
+const SEQS_PER_MILLI: u64 = 1000;
+
+// 時刻順のメッセージを、間隔と件数の上限で会話ウィンドウ（entries の範囲）に分ける
+fn windows(entries: &[Entry], config: &ChatConfig) -> Vec<Range<usize>> {
+    let max_messages = config.max_window_messages.max(1);
+    let mut windows = Vec::new();
+    let mut start = 0;
+
+    for i in 1..entries.len() {
+        let gap = (entries[i].time - entries[i - 1].time).num_seconds();
+        if gap >= config.window_gap_secs || i - start >= max_messages {
+            windows.push(start..i);
+            start = i;
+        }
+    }
+    if start < entries.len() {
+        windows.push(start..entries.len());
+    }
+
+    windows
+}
+
+// 開始時刻をポイント番号にする（同じエクスポートを再投入すると上書きされる）
+// 件数の上限で分けたウィンドウは前のウィンドウと同じミリ秒に始まることがあるため、
+// 同じミリ秒の先行メッセージ数を加えて区別する
+fn window_seq(entries: &[Entry], start: usize) -> u64 {
+    let millis = entries[start].time.timestamp_millis();
+    let same_millis = entries[..start]
+        .iter()
+        .rev()
+        .take_while(|entry| entry.time.timestamp_millis() == millis)
+        .count();
+    millis as u64 * SEQS_PER_MILLI + same_millis as u64
+}
+
+fn window_passage(channel: &str, window: &[Entry], seq: u64) -> Passage {
+    let (first, last) = (&window[0], &window[window.len() - 1]);
+    let participants: BTreeSet<&str> = window.iter().map(|entry| entry.user.as_str()).collect();
+    let text = window
+        .iter()
+        .map(|entry| format!("{}: {}", entry.user, entry.text))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let metadata: HashMap<String, Value> = [
+        ("kind", Value::from("chat")),
+        ("channel", Value::from(channel)),
+        (
+            "participants",
+            Value::from(
+                participants
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect::<Vec<_>>(),
+            ),
+        ),
+        ("start", Value::from(first.time.to_rfc3339())),
+        ("end", Value::from(last.time.to_rfc3339())),
+        ("messages", Value::from(window.len() as i64)),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), value))
+    .collect();
+
+    Passage {
+        seq,
+        text,
+        metadata,
+    }
+}
+
+// チャンネルのメッセージを時刻順に並べ、会話ウィンドウごとのパッセージにする
+fn channel_passages(channel: &str, mut entries: Vec<Entry>, config: &ChatConfig) -> Vec<Passage> {
+    entries.sort_by_key(|entry| entry.time);
+    windows(&entries, config)
+        .into_iter()
+        .map(|window| {
+            let seq = window_seq(&entries, window.start);
+            window_passage(channel, &entries[window], seq)
+        })
+        .collect()
+}
+
+// チャットのエクスポートをチャンネルと時間の間隔で会話ウィンドウにまとめ、公開中のコレクションへ投入
+pub async fn ingest(
+    ingester: &TextIngester,
+    config: &ChatConfig,
+    path: &Path,
+) -> Result<ChatIngested> {
+    let collection_name = ingester.collection().await?;
+    let files = collect_files(path)?;
+    if files.is_empty() {
+        bail!("No chat exports found in {}", path.display());
+    }
+
+    let mut channels: BTreeMap<String, Vec<Entry>> = BTreeMap::new();
+    let mut result = ChatIngested::default();
+    for file in &files {
+        let content = std::fs::read(file)
+            .with_context(|| format!("Failed to read chat export: {}", file.display()))?;
+        let content = ingester.config().encoding.decode(file, &content)?;
+        let export: ChatExport = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse chat export: {}", file.display()))?;
+        let messages = match export {
+            ChatExport::Messages(messages) | ChatExport::Wrapped { messages } => messages,
+        };
+
+        for message in messages {
+            // 参加・退出などの本文のないメッセージは会話に含めない
+            if message.text.trim().is_empty() {
+                continue;
+            }
+            let channel = message
+                .channel
+                .unwrap_or_else(|| default_channel(path, file));
+            channels.entry(channel).or_default().push(Entry {
+                time: message.timestamp.parse()?,
+                user: message.user.unwrap_or_else(|| "unknown".to_string()),
+                text: message.text.trim().to_string(),
+            });
+            result.messages += 1;
+        }
+        result.files += 1;
+    }
+
+    for (channel, entries) in channels {
+        let passages = channel_passages(&channel, entries, config);
+
+        let points = ingester
+            .embed(
+                &format!("chat:{}", channel),
+                &format!("#{}", channel),
+                &passages,
+            )
+            .await?;
+        result.points += points.len() as u64;
+        ingester.upsert(&collection_name, points).await?;
+        result.channels += 1;
+        result.windows += passages.len() as u64;
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 基準時刻からの秒数とユーザー、本文
+    fn entries(messages: &[(f64, &str, &str)]) -> Vec<Entry> {
+        messages
+            .iter()
+            .map(|&(seconds, user, text)| Entry {
+                time: Timestamp::Seconds(1_700_000_000.0 + seconds)
+                    .parse()
+                    .unwrap(),
+                user: user.to_string(),
+                text: text.to_string(),
+            })
+            .collect()
+    }
+
+    fn config(window_gap_secs: i64, max_window_messages: usize) -> ChatConfig {
+        ChatConfig {
+            window_gap_secs,
+            max_window_messages,
+        }
+    }
+
+    #[test]
+    fn a_time_gap_starts_a_new_window() {
+        let entries = entries(&[
+            (0.0, "alice", "a"),
+            (30.0, "bob", "b"),
+            (630.0, "alice", "c"),
+            (1229.0, "bob", "d"),
+            (1830.0, "alice", "e"),
+        ]);
+
+        // 間隔が window_gap_secs 以上で分け、未満なら同じ会話にする
+        assert_eq!(windows(&entries, &config(600, 30)), [0..2, 2..4, 4..5]);
+        assert_eq!(windows(&entries, &config(601, 30)), [0..4, 4..5]);
+        assert_eq!(windows(&[], &config(600, 30)), Vec::<Range<usize>>::new());
+    }
+
+    #[test]
+    fn max_messages_starts_a_new_window() {
+        let entries = entries(&[
+            (0.0, "alice", "a"),
+            (1.0, "bob", "b"),
+            (2.0, "alice", "c"),
+            (3.0, "bob", "d"),
+            (4.0, "alice", "e"),
+        ]);
+
+        assert_eq!(windows(&entries, &config(600, 2)), [0..2, 2..4, 4..5]);
+        // 0 は 1 として扱う
+        assert_eq!(windows(&entries, &config(600, 0)).len(), 5);
+    }
+
+    #[test]
+    fn windows_follow_message_time_order() {
+        let passages = channel_passages(
+            "general",
+            entries(&[
+                (700.0, "carol", "later"),
+                (0.0, "alice", "first"),
+                (10.0, "bob", "second"),
+            ]),
+            &config(600, 30),
+        );
+
+        let texts: Vec<&str> = passages
+            .iter()
+            .map(|passage| passage.text.as_str())
+            .collect();
+        assert_eq!(texts, ["alice: first\nbob: second", "carol: later"]);
+        assert!(passages[0].seq < passages[1].seq);
+    }
+
+    #[test]
+    fn windows_starting_in_the_same_millisecond_get_distinct_seqs() {
+        let passages = channel_passages(
+            "general",
+            entries(&[
+                (0.0, "alice", "a"),
+                (0.0, "bob", "b"),
+                (0.0, "carol", "c"),
+                (1.0, "alice", "d"),
+            ]),
+            &config(600, 1),
+        );
+
+        let seqs: Vec<u64> = passages.iter().map(|passage| passage.seq).collect();
+        let start = 1_700_000_000_000 * SEQS_PER_MILLI;
+        assert_eq!(
+            seqs,
+            [start, start + 1, start + 2, start + SEQS_PER_MILLI * 1000]
+        );
+    }
+}
@@ -0,0 +1,327 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::atomic;
+
+// 再開位置をまたぐ親セクション（先頭の行番号と、再開前に決まった親ID）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenSection {
+    pub start: u64,
+    pub parent_id: String,
+}
+
+// ファイルごとのコミット済み位置
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileCheckpoint {
+    // 先頭から連続してupsert済みのチャンク数
+    pub committed_chunks: u64,
+    // 上記チャンクに含まれる行数（再開時に読み飛ばす行数）
+    pub committed_lines: u64,
+    // 最後にコミットした行の親セクション（再開後も同じ親IDを使い、本文を二重に保存しない）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_section: Option<OpenSection>,
+}
+
+// 永続化するチェックポイント
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub collection: String,
+    pub completed_files: BTreeSet<String>,
+    pub files: BTreeMap<String, FileCheckpoint>,
+}
+
+impl Checkpoint {
+    pub fn new(collection: &str) -> Self {
+        Self {
+            collection: collection.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read checkpoint: {}", path.display()))?;
+        let checkpoint = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse checkpoint: {}", path.display()))?;
+        Ok(Some(checkpoint))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_vec_pretty(self).context("Failed to serialize checkpoint")?;
+        atomic::write(path, &content, "checkpoint")
+    }
+
+    pub fn remove(path: &Path) -> Result<()> {
+        match std::fs::remove_file(path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => {
+                Err(e).with_context(|| format!("Failed to remove checkpoint: {}", path.display()))
+            }
+        }
+    }
+
+    pub fn is_completed(&self, source: &str) -> bool {
+        self.completed_files.contains(source)
+    }
+
+    pub fn resume_point(&self, source: &str) -> FileCheckpoint {
+        self.files.get(source).cloned().unwrap_or_default()
+    }
+}
+
+// 実行中のファイルのコミット状況
+#[derive(Default)]
+struct FileTracking {
+    watermark: FileCheckpoint,
+    // 連続していないコミット済みチャンク（チャンク番号 -> 行数と最後の行の親セクション）
+    pending: BTreeMap<u64, (u64, Option<OpenSection>)>,
+    // 読み込み完了時に確定する総チャンク数
+    total_chunks: Option<u64>,
+}
+
+struct TrackerState {
+    checkpoint: Checkpoint,
+    files: HashMap<usize, FileTracking>,
+}
+
+// upsert完了に応じてチェックポイントを更新・保存する
+pub struct CheckpointTracker {
+    path: PathBuf,
+    sources: Vec<String>,
+    state: Mutex<TrackerState>,
+}
+
+impl CheckpointTracker {
+    pub fn new(path: &Path, sources: Vec<String>, checkpoint: Checkpoint) -> Self {
+        let files = sources
+            .iter()
+            .enumerate()
+            .map(|(file, source)| {
+                let tracking = FileTracking {
+                    watermark: checkpoint.resume_point(source),
+                    ..FileTracking::default()
+                };
+                (file, tracking)
+            })
+            .collect();
+
+        Self {
+            path: path.to_path_buf(),
+            sources,
+            state: Mutex::new(TrackerState { checkpoint, files }),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, TrackerState> {
+        self.state.lock().expect("checkpoint state poisoned")
+    }
+
+    // ファイルの再開位置
+    pub fn resume_point(&self, file: usize) -> FileCheckpoint {
+        self.state()
+            .files
+            .get(&file)
+            .map(|tracking| tracking.watermark.clone())
+            .unwrap_or_default()
+    }
+
+    // ファイルの全チャンクが作成された（総チャンク数が確定）
+    pub fn file_chunked(&self, file: usize, total_chunks: u64) -> Result<()> {
+        let mut state = self.state();
+        if let Some(tracking) = state.files.get_mut(&file) {
+            tracking.total_chunks = Some(total_chunks);
+        }
+
+        if self.complete_if_done(&mut state, file) {
+            state.checkpoint.save(&self.path)?;
+        }
+        Ok(())
+    }
+
+    // upsert済みのチャンク（ファイル, チャンク番号, 行数, 最後の行の親セクション）を反映して保存
+    pub fn committed(&self, chunks: &[(usize, u64, u64, Option<OpenSection>)]) -> Result<()> {
+        let mut state = self.state();
+
+        for (file, chunk, lines, section) in chunks {
+            let Some(tracking) = state.files.get_mut(file) else {
+                continue;
+            };
+
+            tracking.pending.insert(*chunk, (*lines, section.clone()));
+            while let Some((lines, section)) = tracking
+                .pending
+                .remove(&tracking.watermark.committed_chunks)
+            {
+                tracking.watermark.committed_chunks += 1;
+                tracking.watermark.committed_lines += lines;
+                tracking.watermark.open_section = section;
+            }
+
+            let watermark = tracking.watermark.clone();
+            let source = self.sources[*file].clone();
+            state.checkpoint.files.insert(source, watermark);
+            self.complete_if_done(&mut state, *file);
+        }
+
+        state.checkpoint.save(&self.path)
+    }
+
+    // 全チャンクがコミット済みなら完了ファイルへ移す
+    fn complete_if_done(&self, state: &mut TrackerState, file: usize) -> bool {
+        let done = state.files.get(&file).is_some_and(|tracking| {
+            tracking.total_chunks == Some(tracking.watermark.committed_chunks)
+        });
+
+        if done {
+            let source = &self.sources[file];
+            state.files.remove(&file);
+            state.checkpoint.files.remove(source);
+            state.checkpoint.completed_files.insert(source.clone());
+        }
+        done
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // テストごとに別のチェックポイントファイルを使う
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            Self(std::env::temp_dir().join(format!(
+                "vectorium-checkpoint-{}-{}.json",
+                std::process::id(),
+                name
+            )))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn tracker(path: &TempPath) -> CheckpointTracker {
+        CheckpointTracker::new(
+            &path.0,
+            vec!["a.md".to_string(), "b.md".to_string()],
+            Checkpoint::new("knowledge_v1"),
+        )
+    }
+
+    fn saved(path: &TempPath) -> Checkpoint {
+        Checkpoint::load(&path.0).unwrap().unwrap()
+    }
+
+    #[test]
+    fn out_of_order_commits_advance_the_watermark_once_contiguous() {
+        let path = TempPath::new("out-of-order");
+        let tracker = tracker(&path);
+
+        tracker
+            .committed(&[(0, 1, 5, None), (0, 2, 7, None)])
+            .unwrap();
+        assert_eq!(tracker.resume_point(0), FileCheckpoint::default());
+
+        tracker.committed(&[(0, 0, 3, None)]).unwrap();
+        let resume_point = tracker.resume_point(0);
+        assert_eq!(resume_point.committed_chunks, 3);
+        assert_eq!(resume_point.committed_lines, 15);
+        assert_eq!(saved(&path).resume_point("a.md"), resume_point);
+    }
+
+    #[test]
+    fn a_gap_holds_the_watermark_back() {
+        let path = TempPath::new("gap");
+        let tracker = tracker(&path);
+
+        tracker
+            .committed(&[(0, 0, 4, None), (0, 2, 4, None), (0, 3, 4, None)])
+            .unwrap();
+        let resume_point = tracker.resume_point(0);
+        assert_eq!(resume_point.committed_chunks, 1);
+        assert_eq!(resume_point.committed_lines, 4);
+        // 他のファイルの位置には影響しない
+        assert_eq!(tracker.resume_point(1), FileCheckpoint::default());
+    }
+
+    #[test]
+    fn file_completes_when_chunked_before_the_final_commit() {
+        let path = TempPath::new("chunked-first");
+        let tracker = tracker(&path);
+
+        tracker.committed(&[(0, 0, 4, None)]).unwrap();
+        tracker.file_chunked(0, 2).unwrap();
+        assert!(!saved(&path).is_completed("a.md"));
+
+        tracker.committed(&[(0, 1, 2, None)]).unwrap();
+        let checkpoint = saved(&path);
+        assert!(checkpoint.is_completed("a.md"));
+        assert!(!checkpoint.files.contains_key("a.md"));
+    }
+
+    #[test]
+    fn file_completes_when_chunked_after_the_final_commit() {
+        let path = TempPath::new("chunked-last");
+        let tracker = tracker(&path);
+
+        tracker
+            .committed(&[(0, 0, 4, None), (0, 1, 2, None)])
+            .unwrap();
+        assert!(!saved(&path).is_completed("a.md"));
+
+        tracker.file_chunked(0, 2).unwrap();
+        let checkpoint = saved(&path);
+        assert!(checkpoint.is_completed("a.md"));
+        assert!(!checkpoint.files.contains_key("a.md"));
+        assert!(!checkpoint.is_completed("b.md"));
+    }
+
+    #[test]
+    fn saved_checkpoint_resumes_where_it_left_off() {
+        let path = TempPath::new("round-trip");
+        let section = OpenSection {
+            start: 3,
+            parent_id: "parent".to_string(),
+        };
+        {
+            let tracker = tracker(&path);
+            tracker.committed(&[(1, 0, 1, None)]).unwrap();
+            tracker.file_chunked(1, 1).unwrap();
+            tracker
+                .committed(&[(0, 0, 4, None), (0, 1, 3, Some(section.clone()))])
+                .unwrap();
+        }
+
+        let checkpoint = saved(&path);
+        assert_eq!(checkpoint.collection, "knowledge_v1");
+        assert!(checkpoint.is_completed("b.md"));
+
+        // 読み込んだチェックポイントから続きの位置と親セクションを引き継ぐ
+        let tracker = CheckpointTracker::new(
+            &path.0,
+            vec!["a.md".to_string(), "b.md".to_string()],
+            checkpoint,
+        );
+        assert_eq!(
+            tracker.resume_point(0),
+            FileCheckpoint {
+                committed_chunks: 2,
+                committed_lines: 7,
+                open_section: Some(section),
+            }
+        );
+    }
+}
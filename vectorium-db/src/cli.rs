@@ -0,0 +1,159 @@
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+use vectorium_db::config::DEFAULT_CONFIG_PATH;
+use vectorium_db::transfer::Format;
+
+// コマンドライン引数（サブコマンド省略時はインジェスト）
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "テキストファイルを埋め込み、Qdrantに登録します",
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    /// 設定ファイルのパス
+    #[arg(long, global = true, default_value = DEFAULT_CONFIG_PATH)]
+    pub config: PathBuf,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub ingest: IngestArgs,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// 新しいバージョンのコレクションを作成し、検証後にエイリアスを切り替える
+    Ingest(IngestArgs),
+    /// エイリアスを以前のバージョンのコレクションへ戻す
+    Rollback {
+        /// 戻す先のバージョン番号（省略時は直前のバージョン）
+        #[arg(long)]
+        to: Option<u32>,
+    },
+    /// 公開中のコレクションの全ポイントをJSONLまたはParquetへ書き出す
+    Export {
+        path: PathBuf,
+        /// 省略時は拡張子から判定（.parquet 以外はJSONL）
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+    },
+    /// エクスポートしたファイルを新しいバージョンへ取り込み、エイリアスを切り替える
+    Import {
+        path: PathBuf,
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+        /// 1回のupsertで送るポイント数
+        #[arg(long, default_value_t = 256)]
+        batch_size: usize,
+    },
+    /// 設定ファイルの [tuning] を既存のコレクションに反映する（再構築は不要）
+    UpdateCollection {
+        /// 省略時は公開中のコレクション
+        #[arg(long)]
+        collection: Option<String>,
+    },
+    /// デッドレターに記録したチャンクを埋め込み直して登録する
+    Replay {
+        /// 省略時は設定の failures.dead_letter_path
+        #[arg(long)]
+        dead_letter: Option<PathBuf>,
+    },
+    /// ローカルのgitリポジトリのコミットメッセージを公開中のコレクションへ投入（前回以降の差分のみ）
+    Git {
+        repo: PathBuf,
+        /// この ref の時点のファイルも投入（[git] file_patterns に一致するもの、複数指定可）
+        #[arg(long)]
+        files_at: Vec<String>,
+        /// 前回の位置を無視して全コミットを投入し直す
+        #[arg(long)]
+        full: bool,
+        /// 投入先のテナント（省略時は default_tenant）
+        #[arg(long)]
+        tenant: Option<String>,
+    },
+    /// mbox ファイルまたは Maildir ディレクトリのメールを公開中のコレクションへ投入
+    Mail {
+        path: PathBuf,
+        /// 投入先のテナント（省略時は default_tenant）
+        #[arg(long)]
+        tenant: Option<String>,
+    },
+    /// チャットのJSONエクスポート（ディレクトリの場合は配下の *.json）を会話ごとに投入
+    Chat {
+        path: PathBuf,
+        /// 投入先のテナント（省略時は default_tenant）
+        #[arg(long)]
+        tenant: Option<String>,
+    },
+    /// 古いスキーマバージョンのポイントのペイロードを書き換える（埋め込み直さない）
+    Migrate {
+        /// 省略時は公開中のコレクション
+        #[arg(long)]
+        collection: Option<String>,
+        /// 1回に読み書きするポイント数
+        #[arg(long, default_value_t = 256)]
+        batch_size: u32,
+        /// 書き換えず、移行が必要なポイント数のみ表示
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+// インジェストのオプション
+#[derive(Debug, Args)]
+pub struct IngestArgs {
+    /// 進捗表示を抑制し、最終サマリーのみ出力
+    #[arg(long, short, conflicts_with = "json")]
+    pub quiet: bool,
+
+    /// 最終サマリーをJSONで標準出力に出力（CIログ向け）
+    #[arg(long)]
+    pub json: bool,
+
+    /// 前回中断したインジェストをチェックポイントから再開
+    #[arg(long, conflicts_with = "dry_run")]
+    pub resume: bool,
+
+    /// モデル読み込みや書き込みを行わず、投入計画と既存ポイントとの差分のみ表示
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// 実行結果のJSONレポートの書き出し先（省略時は設定の report.path）
+    #[arg(long, value_name = "PATH", conflicts_with = "dry_run")]
+    pub report: Option<PathBuf>,
+
+    /// 埋め込むモデル（カンマ区切り、省略時は [vectors] models の全て）
+    #[arg(long, value_delimiter = ',')]
+    pub models: Vec<String>,
+
+    /// "-" の場合は標準入力（UTF-8）のテキストを公開中のコレクションへ投入
+    #[arg(
+        value_name = "-",
+        value_parser = ["-"],
+        requires = "title",
+        conflicts_with_all = ["resume", "dry_run", "report"]
+    )]
+    pub input: Option<String>,
+
+    /// 標準入力のタイトル（同じタイトルで再投入すると置き換える）
+    #[arg(long, requires = "input")]
+    pub title: Option<String>,
+
+    /// 標準入力を投入するテナント（省略時は default_tenant）
+    #[arg(long, requires = "input")]
+    pub tenant: Option<String>,
+
+    /// 標準入力のポイントに付けるペイロード（key=value、複数指定可）
+    #[arg(long = "meta", value_name = "KEY=VALUE", value_parser = parse_metadata, requires = "input")]
+    pub metadata: Vec<(String, String)>,
+}
+
+fn parse_metadata(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE: {}", value)),
+    }
+}
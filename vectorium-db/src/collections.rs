@@ -0,0 +1,530 @@
+use anyhow::{Context, Result, bail};
+use qdrant_client::Qdrant;
+use qdrant_client::qdrant::value::Kind;
+use qdrant_client::qdrant::{
+    CollectionConfig, CountPointsBuilder, CreateAliasBuilder, CreateCollectionBuilder,
+    CreateFieldIndexCollectionBuilder, FieldType, GetPointsBuilder, PayloadIncludeSelector,
+    PayloadSchemaType, PointId, PointStruct, ScrollPointsBuilder, Vector, VectorOutput, Vectors,
+    VectorsOutput, vector_output, vectors_output,
+};
+use std::collections::HashMap;
+
+use crate::config::ProcessingConfig;
+use crate::pipeline;
+use crate::schema::PayloadSchema;
+use crate::tuning::TuningConfig;
+use crate::vectors::VectorsConfig;
+
+// 無名ベクトルの次元数（DEFAULT_MODEL の出力次元）
+pub const VECTOR_DIMENSION: u64 = 512;
+
+// バージョン付きコレクション名（例: knowledge_v17）
+pub fn version_name(alias: &str, version: u32) -> String {
+    format!("{}_v{}", alias, version)
+}
+
+pub fn parse_version(alias: &str, collection_name: &str) -> Option<u32> {
+    collection_name
+        .strip_prefix(alias)?
+        .strip_prefix("_v")?
+        .parse()
+        .ok()
+}
+
+// 公開前のビルドを指すエイリアス（バージョン名とは重ならない）
+fn build_alias(alias: &str) -> String {
+    format!("{}_build", alias)
+}
+
+// 既存のバージョン番号（昇順）
+pub async fn versions(client: &Qdrant, alias: &str) -> Result<Vec<u32>> {
+    let response = client
+        .list_collections()
+        .await
+        .context("Failed to list collections")?;
+
+    let mut versions: Vec<u32> = response
+        .collections
+        .iter()
+        .filter_map(|collection| parse_version(alias, &collection.name))
+        .collect();
+    versions.sort_unstable();
+    Ok(versions)
+}
+
+// エイリアスが指しているコレクション
+pub async fn alias_target(client: &Qdrant, alias: &str) -> Result<Option<String>> {
+    let response = client
+        .list_aliases()
+        .await
+        .context("Failed to list aliases")?;
+
+    Ok(response
+        .aliases
+        .into_iter()
+        .find(|description| description.alias_name == alias)
+        .map(|description| description.collection_name))
+}
+
+// 読み取り対象の実コレクション（エイリアス導入前の実コレクションにも対応）
+pub async fn resolve(client: &Qdrant, alias: &str) -> Result<Option<String>> {
+    if let Some(target) = alias_target(client, alias).await? {
+        return Ok(Some(target));
+    }
+
+    let exists = client
+        .collection_exists(alias)
+        .await
+        .context("Failed to connect to Qdrant")?;
+    Ok(exists.then(|| alias.to_string()))
+}
+
+// 次のバージョンのコレクションを作成
+pub async fn create_next_version(
+    client: &Qdrant,
+    alias: &str,
+    schema: &PayloadSchema,
+    vectors: &VectorsConfig,
+    tuning: &TuningConfig,
+) -> Result<String> {
+    // 公開されずに残った前回のビルドは使わないため削除（ロールバックで外したバージョンは garbage_collect に任せる）
+    let building = build_alias(alias);
+    if let Some(abandoned) = alias_target(client, &building).await? {
+        client
+            .delete_collection(abandoned.as_str())
+            .await
+            .with_context(|| format!("Failed to delete abandoned collection: {}", abandoned))?;
+    }
+
+    let versions = versions(client, alias).await?;
+    let next = versions.last().map_or(1, |latest| latest + 1);
+    let collection_name = version_name(alias, next);
+    initialize_collection(client, &collection_name, schema, vectors, tuning).await?;
+
+    // 公開するまでビルド中の印としてエイリアスを付けておく
+    client
+        .create_alias(CreateAliasBuilder::new(&collection_name, &building))
+        .await
+        .with_context(|| format!("Failed to point alias {} to {}", building, collection_name))?;
+    Ok(collection_name)
+}
+
+// コレクション初期化
+async fn initialize_collection(
+    client: &Qdrant,
+    collection_name: &str,
+    schema: &PayloadSchema,
+    vectors: &VectorsConfig,
+    tuning: &TuningConfig,
+) -> Result<()> {
+    let request = CreateCollectionBuilder::new(collection_name)
+        .vectors_config(vectors.collection_config(tuning.on_disk_vectors));
+
+    client
+        .create_collection(tuning.apply(request))
+        .await
+        .with_context(|| format!("Failed to create collection: {}", collection_name))?;
+
+    // フィルタ対象フィールドのペイロードインデックスを作成
+    schema.create_indexes(client, collection_name).await?;
+
+    Ok(())
+}
+
+async fn count_points(client: &Qdrant, collection_name: &str) -> Result<u64> {
+    Ok(client
+        .count(CountPointsBuilder::new(collection_name).exact(true))
+        .await
+        .with_context(|| format!("Failed to count points in {}", collection_name))?
+        .result
+        .map_or(0, |result| result.count))
+}
+
+// 公開前の検証（空でないこと、登録したポイント数と一致すること）
+async fn validate(
+    client: &Qdrant,
+    collection_name: &str,
+    upserted: u64,
+    resumed: bool,
+) -> Result<u64> {
+    let count = count_points(client, collection_name).await?;
+    if count == 0 {
+        bail!("Refusing to publish empty collection: {}", collection_name);
+    }
+    // 再開時は前回の実行で登録済みのポイントも含まれる
+    if count < upserted || (!resumed && count != upserted) {
+        bail!(
+            "Collection {} has {} points but {} were upserted",
+            collection_name,
+            count,
+            upserted
+        );
+    }
+
+    Ok(count)
+}
+
+// エイリアス導入前の実コレクションを {alias}_v0 へ複製してから削除する（ロールバック先として残す）
+async fn retire_legacy_collection(
+    client: &Qdrant,
+    alias: &str,
+    config: &ProcessingConfig,
+) -> Result<String> {
+    let legacy = version_name(alias, 0);
+    // 前回の複製が途中で止まっていれば作り直す
+    if client
+        .collection_exists(&legacy)
+        .await
+        .context("Failed to connect to Qdrant")?
+    {
+        client
+            .delete_collection(legacy.as_str())
+            .await
+            .with_context(|| format!("Failed to delete incomplete copy: {}", legacy))?;
+    }
+
+    // 設定（ベクトル、HNSW、量子化、オンディスク、オプティマイザ）とペイロードインデックスをそのまま引き継ぐ
+    let info = client
+        .collection_info(alias)
+        .await
+        .with_context(|| format!("Failed to get collection info: {}", alias))?
+        .result
+        .with_context(|| format!("Collection {} has no info", alias))?;
+    let collection_config = info
+        .config
+        .with_context(|| format!("Collection {} has no configuration", alias))?;
+    client
+        .create_collection(copy_config(&legacy, collection_config)?)
+        .await
+        .with_context(|| format!("Failed to create collection: {}", legacy))?;
+    for (name, index) in info.payload_schema {
+        let field_type = field_type(index.data_type)
+            .with_context(|| format!("Unsupported payload index on {}: {}", alias, name))?;
+        let mut request = CreateFieldIndexCollectionBuilder::new(&legacy, &name, field_type);
+        if let Some(params) = index.params.and_then(|params| params.index_params) {
+            request = request.field_index_params(params);
+        }
+        client
+            .create_field_index(request.wait(true))
+            .await
+            .with_context(|| format!("Failed to create payload index: {}", name))?;
+    }
+
+    carry_over(client, alias, &legacy, |_| true, config).await?;
+    let (expected, copied) = (
+        count_points(client, alias).await?,
+        count_points(client, &legacy).await?,
+    );
+    if copied != expected {
+        bail!(
+            "Copied {} of {} points from legacy collection {} into {}",
+            copied,
+            expected,
+            alias,
+            legacy
+        );
+    }
+
+    // 同名のエイリアスは実コレクションを削除するまで作れないため、エイリアスを作るまでの間は検索できない
+    client
+        .delete_collection(alias)
+        .await
+        .with_context(|| format!("Failed to delete legacy collection: {}", alias))?;
+    Ok(legacy)
+}
+
+// 取得したコレクション設定から同じ設定のコレクションを作る要求を組み立てる
+fn copy_config(collection_name: &str, config: CollectionConfig) -> Result<CreateCollectionBuilder> {
+    let params = config
+        .params
+        .with_context(|| format!("Collection {} has no parameters", collection_name))?;
+    #[allow(deprecated)]
+    let on_disk_payload = params.on_disk_payload;
+
+    let mut request = CreateCollectionBuilder::new(collection_name)
+        .shard_number(params.shard_number)
+        .on_disk_payload(on_disk_payload);
+    if let Some(vectors) = params.vectors_config {
+        request = request.vectors_config(vectors);
+    }
+    if let Some(sparse) = params.sparse_vectors_config {
+        request = request.sparse_vectors_config(sparse);
+    }
+    if let Some(replication) = params.replication_factor {
+        request = request.replication_factor(replication);
+    }
+    if let Some(consistency) = params.write_consistency_factor {
+        request = request.write_consistency_factor(consistency);
+    }
+    if let Some(hnsw) = config.hnsw_config {
+        request = request.hnsw_config(hnsw);
+    }
+    if let Some(optimizers) = config.optimizer_config {
+        request = request.optimizers_config(optimizers);
+    }
+    if let Some(wal) = config.wal_config {
+        request = request.wal_config(wal);
+    }
+    if let Some(quantization) = config
+        .quantization_config
+        .and_then(|quantization| quantization.quantization)
+    {
+        request = request.quantization_config(quantization);
+    }
+    Ok(request)
+}
+
+// 取得したペイロードインデックスの種類を作成時の種類へ変換
+fn field_type(data_type: i32) -> Option<FieldType> {
+    match PayloadSchemaType::try_from(data_type).ok()? {
+        PayloadSchemaType::Keyword => Some(FieldType::Keyword),
+        PayloadSchemaType::Integer => Some(FieldType::Integer),
+        PayloadSchemaType::Float => Some(FieldType::Float),
+        PayloadSchemaType::Geo => Some(FieldType::Geo),
+        PayloadSchemaType::Text => Some(FieldType::Text),
+        PayloadSchemaType::Bool => Some(FieldType::Bool),
+        PayloadSchemaType::Datetime => Some(FieldType::Datetime),
+        PayloadSchemaType::Uuid => Some(FieldType::Uuid),
+        PayloadSchemaType::UnknownType => None,
+    }
+}
+
+// エイリアスの切り替え結果
+pub struct Switched {
+    pub previous: Option<String>,
+    // エイリアス導入前の実コレクションを {alias}_v0 へ移した（削除からエイリアス作成までは検索できなかった）
+    pub retired_legacy: bool,
+}
+
+// エイリアスを切り替え、以前の参照先を返す
+pub async fn switch_alias(
+    client: &Qdrant,
+    alias: &str,
+    collection_name: &str,
+    config: &ProcessingConfig,
+) -> Result<Switched> {
+    let mut previous = alias_target(client, alias).await?;
+
+    // エイリアス導入前の実コレクションがあると同名のエイリアスを作れない
+    let retired_legacy = previous.is_none()
+        && client
+            .collection_exists(alias)
+            .await
+            .context("Failed to connect to Qdrant")?;
+    if retired_legacy {
+        previous = Some(retire_legacy_collection(client, alias, config).await?);
+    }
+
+    // 既存のエイリアスは1回の操作で付け替わる（実コレクションを移した直後を除き、検索が空になる瞬間がない）
+    client
+        .create_alias(CreateAliasBuilder::new(collection_name, alias))
+        .await
+        .with_context(|| format!("Failed to point alias {} to {}", alias, collection_name))?;
+
+    Ok(Switched {
+        previous,
+        retired_legacy,
+    })
+}
+
+// 引き継ぎで1回に読み込むポイント数
+const CARRY_OVER_BATCH_SIZE: u32 = 256;
+
+// 取得したベクトルを投入用に変換（無名・名前付きのどちらにも対応）
+pub fn vectors_from_output(vectors: VectorsOutput) -> Option<Vectors> {
+    fn into_vector(vector: VectorOutput) -> Vector {
+        match vector.into_vector() {
+            vector_output::Vector::Dense(dense) => dense.into(),
+            vector_output::Vector::Sparse(sparse) => sparse.into(),
+            vector_output::Vector::MultiDense(multi) => multi.into(),
+        }
+    }
+
+    Some(match vectors.vectors_options? {
+        vectors_output::VectorsOptions::Vector(vector) => into_vector(vector).into(),
+        vectors_output::VectorsOptions::Vectors(named) => named
+            .vectors
+            .into_iter()
+            .map(|(name, vector)| (name, into_vector(vector)))
+            .collect::<HashMap<_, _>>()
+            .into(),
+    })
+}
+
+// source（ない場合は None）が carry に一致するポイントを、ベクトルごと別のコレクションへ複製する（複製した数を返す）
+pub async fn carry_over(
+    client: &Qdrant,
+    from: &str,
+    to: &str,
+    carry: impl Fn(Option<&str>) -> bool,
+    config: &ProcessingConfig,
+) -> Result<u64> {
+    let mut carried = 0;
+    let mut offset: Option<PointId> = None;
+    loop {
+        // 先に source だけを読み、対象のポイントのみベクトルを取得する
+        let mut request = ScrollPointsBuilder::new(from)
+            .limit(CARRY_OVER_BATCH_SIZE)
+            .with_payload(PayloadIncludeSelector {
+                fields: vec!["source".to_string()],
+            })
+            .with_vectors(false);
+        if let Some(offset) = offset.take() {
+            request = request.offset(offset);
+        }
+        let response = client
+            .scroll(request)
+            .await
+            .with_context(|| format!("Failed to scroll points in {}", from))?;
+
+        let ids: Vec<PointId> = response
+            .result
+            .into_iter()
+            .filter(|point| {
+                carry(
+                    match point
+                        .payload
+                        .get("source")
+                        .and_then(|value| value.kind.as_ref())
+                    {
+                        Some(Kind::StringValue(source)) => Some(source),
+                        _ => None,
+                    },
+                )
+            })
+            .filter_map(|point| point.id)
+            .collect();
+
+        if !ids.is_empty() {
+            let retrieved = client
+                .get_points(
+                    GetPointsBuilder::new(from, ids)
+                        .with_payload(true)
+                        .with_vectors(true),
+                )
+                .await
+                .with_context(|| format!("Failed to retrieve points from {}", from))?
+                .result;
+
+            let mut points = Vec::with_capacity(retrieved.len());
+            for point in retrieved {
+                let vectors = point
+                    .vectors
+                    .and_then(vectors_from_output)
+                    .with_context(|| format!("Point {:?} in {} has no vectors", point.id, from))?;
+                points.push(PointStruct {
+                    id: point.id,
+                    payload: point.payload,
+                    vectors: Some(vectors),
+                });
+            }
+            carried += points.len() as u64;
+            pipeline::upsert_points(client, to, points, config)
+                .await
+                .with_context(|| format!("Failed to carry points from {} into {}", from, to))?;
+        }
+
+        match response.next_page_offset {
+            Some(next) => offset = Some(next),
+            None => break,
+        }
+    }
+
+    Ok(carried)
+}
+
+// 公開結果
+pub struct Published {
+    pub count: u64,
+    pub previous: Option<String>,
+    pub retired_legacy: bool,
+    pub deleted: Vec<String>,
+}
+
+// 検証してエイリアスを切り替え、古いバージョンを削除
+pub async fn publish(
+    client: &Qdrant,
+    alias: &str,
+    collection_name: &str,
+    upserted: u64,
+    resumed: bool,
+    keep: usize,
+    config: &ProcessingConfig,
+) -> Result<Published> {
+    let count = validate(client, collection_name, upserted, resumed).await?;
+    let switched = switch_alias(client, alias, collection_name, config).await?;
+
+    // 公開したバージョンからビルド中の印を外す
+    let building = build_alias(alias);
+    if alias_target(client, &building).await?.as_deref() == Some(collection_name) {
+        client
+            .delete_alias(building.as_str())
+            .await
+            .with_context(|| format!("Failed to delete alias: {}", building))?;
+    }
+    let deleted = garbage_collect(client, alias, keep).await?;
+
+    Ok(Published {
+        count,
+        previous: switched.previous,
+        retired_legacy: switched.retired_legacy,
+        deleted,
+    })
+}
+
+// 現在のバージョンと直前の keep - 1 個を残し、古いバージョンを削除
+async fn garbage_collect(client: &Qdrant, alias: &str, keep: usize) -> Result<Vec<String>> {
+    let Some(current) = alias_target(client, alias)
+        .await?
+        .and_then(|target| parse_version(alias, &target))
+    else {
+        return Ok(Vec::new());
+    };
+
+    let older: Vec<u32> = versions(client, alias)
+        .await?
+        .into_iter()
+        .filter(|&version| version < current)
+        .collect();
+    let retained = keep.saturating_sub(1).min(older.len());
+
+    let mut deleted = Vec::new();
+    for &version in &older[..older.len() - retained] {
+        let name = version_name(alias, version);
+        client
+            .delete_collection(name.as_str())
+            .await
+            .with_context(|| format!("Failed to delete old collection: {}", name))?;
+        deleted.push(name);
+    }
+
+    Ok(deleted)
+}
+
+// エイリアスを以前のバージョンへ戻す（未指定なら直前のバージョン）
+pub async fn rollback(
+    client: &Qdrant,
+    alias: &str,
+    to: Option<u32>,
+    config: &ProcessingConfig,
+) -> Result<String> {
+    let current = alias_target(client, alias)
+        .await?
+        .and_then(|target| parse_version(alias, &target));
+    let versions = versions(client, alias).await?;
+
+    let target = match to {
+        Some(version) if versions.contains(&version) => version,
+        Some(version) => bail!("Collection {} does not exist", version_name(alias, version)),
+        None => versions
+            .iter()
+            .rev()
+            .copied()
+            .find(|&version| current.is_some_and(|current| version < current))
+            .with_context(|| format!("No previous version of {} to roll back to", alias))?,
+    };
+
+    let collection_name = version_name(alias, target);
+    switch_alias(client, alias, &collection_name, config).await?;
+    Ok(collection_name)
+}
@@ -0,0 +1,151 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use vectorium_common::EmbeddingPoolConfig;
+
+use crate::chat::ChatConfig;
+use crate::context::ContextConfig;
+use crate::dedup::DedupConfig;
+use crate::encoding::EncodingConfig;
+use crate::failures::FailureConfig;
+use crate::git::GitConfig;
+use crate::normalize::NormalizeConfig;
+use crate::parents::ParentConfig;
+use crate::report::ReportConfig;
+use crate::schema::PayloadSchema;
+use crate::tuning::TuningConfig;
+use crate::vectors::VectorsConfig;
+
+// 設定ファイルのデフォルトパス（存在しない場合はデフォルト設定を使用）
+pub const DEFAULT_CONFIG_PATH: &str = "vectorium.toml";
+
+// 設定構造体でマジックナンバーを排除
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ProcessingConfig {
+    pub chunk_size: usize,
+    pub batch_size: usize,
+    pub buffer_size: usize,
+    // パイプライン各ステージの並行数（embed_workers が 0 の場合はモデルインスタンス数）
+    pub readers: usize,
+    pub embed_workers: usize,
+    pub upsert_writers: usize,
+    // ステージ間チャネルの容量（チャンク数）
+    pub queue_depth: usize,
+    // 1回のupsertリクエストの推定サイズ上限（gRPCのメッセージ上限より小さくする）
+    pub max_request_bytes: usize,
+}
+
+impl Default for ProcessingConfig {
+    fn default() -> Self {
+        Self {
+            chunk_size: 3000,
+            batch_size: 5,
+            buffer_size: 64 * 1024,
+            readers: 2,
+            embed_workers: 0,
+            upsert_writers: 2,
+            queue_depth: 4,
+            max_request_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+// ドライランでの見積もり設定
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PlanConfig {
+    // モデルインスタンス1つあたりの埋め込み速度（文/秒）
+    pub sentences_per_sec: f64,
+}
+
+impl Default for PlanConfig {
+    fn default() -> Self {
+        Self {
+            sentences_per_sec: 40.0,
+        }
+    }
+}
+
+// テナントごとのソース（tenant_id を付けて同じコレクションに投入）
+#[derive(Debug, Clone, Deserialize)]
+pub struct TenantSources {
+    pub id: String,
+    pub sources: Vec<String>,
+}
+
+// インジェスター全体の設定
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // 検索側が参照するエイリアス名（実体は {collection}_v{N} のコレクション）
+    pub collection: String,
+    // 公開中を含めて残すバージョン数（ロールバック用）
+    pub keep_versions: usize,
+    pub sources: Vec<String>,
+    // sources のポイントに付けるテナント
+    pub default_tenant: String,
+    // 他のテナントのソース（全テナントをまとめて1つのバージョンに投入）
+    pub tenants: Vec<TenantSources>,
+    // 再開用チェックポイントの保存先
+    pub checkpoint_path: PathBuf,
+    pub processing: ProcessingConfig,
+    pub embedding: EmbeddingPoolConfig,
+    pub vectors: VectorsConfig,
+    pub tuning: TuningConfig,
+    pub schema: PayloadSchema,
+    pub encoding: EncodingConfig,
+    pub normalize: NormalizeConfig,
+    pub context: ContextConfig,
+    pub parents: ParentConfig,
+    pub dedup: DedupConfig,
+    pub failures: FailureConfig,
+    pub plan: PlanConfig,
+    pub report: ReportConfig,
+    pub git: GitConfig,
+    pub chat: ChatConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            collection: "knowledge".to_string(),
+            keep_versions: 2,
+            sources: vec!["data/*.txt".to_string(), "data/*.md".to_string()],
+            default_tenant: "default".to_string(),
+            tenants: Vec::new(),
+            checkpoint_path: PathBuf::from("vectorium-checkpoint.json"),
+            processing: ProcessingConfig::default(),
+            embedding: EmbeddingPoolConfig::default(),
+            vectors: VectorsConfig::default(),
+            tuning: TuningConfig::default(),
+            schema: PayloadSchema::default(),
+            encoding: EncodingConfig::default(),
+            normalize: NormalizeConfig::default(),
+            context: ContextConfig::default(),
+            parents: ParentConfig::default(),
+            dedup: DedupConfig::default(),
+            failures: FailureConfig::default(),
+            plan: PlanConfig::default(),
+            report: ReportConfig::default(),
+            git: GitConfig::default(),
+            chat: ChatConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    // 設定ファイルを読み込む（ファイルがなければデフォルト）
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse config file: {}", path.display()))
+    }
+}
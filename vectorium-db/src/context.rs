@@ -0,0 +1,304 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::iter::Fuse;
+
+// 埋め込むテキストの前に付ける文脈（短い行でも文書内の位置が分かるようにする）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ContextConfig {
+    pub enabled: bool,
+    // 文書のタイトル（フロントマターの title、なければファイル名）
+    pub title: bool,
+    // Markdownの見出しの階層（例: "インストール > 設定"）
+    pub headings: bool,
+    // フロントマターの要約（summary_keys のうち最初に見つかった値）
+    pub summary: bool,
+    pub summary_keys: Vec<String>,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            title: true,
+            headings: true,
+            summary: true,
+            summary_keys: vec!["summary".to_string(), "description".to_string()],
+        }
+    }
+}
+
+// フロントマターの読み取り状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrontMatter {
+    // 最初の行を待っている
+    Pending,
+    Inside,
+    Done,
+}
+
+// 1ファイル分の行に文脈を付ける（フロントマターの行は文脈としてのみ使い、行としては返さない）
+// 閉じられていないフロントマターはファイルの終わりで本文として返す
+pub struct ContextTracker<I> {
+    lines: Fuse<I>,
+    config: ContextConfig,
+    // フロントマターに title がない場合のタイトル（ファイル名）
+    file_title: String,
+    title: String,
+    summary: Option<String>,
+    // (レベル, 見出し)
+    headings: Vec<(usize, String)>,
+    front_matter: FrontMatter,
+    // 読み込み中のフロントマターの行
+    front_matter_lines: Vec<String>,
+    // 本文として返す残りの行
+    pending: VecDeque<String>,
+}
+
+impl<I: Iterator<Item = Result<String>>> ContextTracker<I> {
+    pub fn new(lines: I, config: &ContextConfig, title: &str) -> Self {
+        Self {
+            lines: lines.fuse(),
+            config: config.clone(),
+            file_title: title.to_string(),
+            title: title.to_string(),
+            summary: None,
+            headings: Vec::new(),
+            front_matter: FrontMatter::Pending,
+            front_matter_lines: Vec::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    // 行を受け取り、本文の行なら Some(文脈) を返す（フロントマターの行は None）
+    // 無効な場合は常に Some(None) を返し、行の扱いを変えない
+    fn read(&mut self, line: &str) -> Option<Option<String>> {
+        if !self.config.enabled {
+            return Some(None);
+        }
+
+        let trimmed = line.trim();
+        match self.front_matter {
+            FrontMatter::Pending if trimmed == "---" => {
+                self.front_matter = FrontMatter::Inside;
+                self.front_matter_lines.push(line.to_string());
+                return None;
+            }
+            FrontMatter::Inside => {
+                if trimmed == "---" || trimmed == "..." {
+                    self.front_matter = FrontMatter::Done;
+                    self.front_matter_lines.clear();
+                } else {
+                    self.read_front_matter(trimmed);
+                    self.front_matter_lines.push(line.to_string());
+                }
+                return None;
+            }
+            _ => self.front_matter = FrontMatter::Done,
+        }
+
+        Some(self.body_context(trimmed))
+    }
+
+    // 見出しの行は親の見出しまでを文脈にする
+    fn body_context(&mut self, trimmed: &str) -> Option<String> {
+        let heading = parse_heading(trimmed);
+        if let Some((level, _)) = heading {
+            self.headings.retain(|(parent, _)| *parent < level);
+        }
+        let context = self.context();
+        if let Some((level, text)) = heading {
+            self.headings.push((level, text.to_string()));
+        }
+        context
+    }
+
+    // 閉じられていないフロントマターは読み取った値を捨て、本文として読み直す
+    fn reopen_front_matter(&mut self) {
+        self.front_matter = FrontMatter::Done;
+        self.title = self.file_title.clone();
+        self.summary = None;
+        self.pending.extend(self.front_matter_lines.drain(..));
+    }
+
+    // フロントマターの "key: value" の行（入れ子や複数行の値は扱わない）
+    fn read_front_matter(&mut self, line: &str) {
+        let Some((key, value)) = line.split_once(':') else {
+            return;
+        };
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+        if value.is_empty() {
+            return;
+        }
+
+        let key = key.trim();
+        if key == "title" {
+            self.title = value.to_string();
+        } else if self.summary.is_none() && self.config.summary_keys.iter().any(|k| k == key) {
+            self.summary = Some(value.to_string());
+        }
+    }
+
+    fn context(&self) -> Option<String> {
+        let mut path = Vec::new();
+        if self.config.title && !self.title.is_empty() {
+            path.push(self.title.as_str());
+        }
+        if self.config.headings {
+            path.extend(self.headings.iter().map(|(_, text)| text.as_str()));
+        }
+
+        let mut lines = Vec::new();
+        if !path.is_empty() {
+            lines.push(path.join(" > "));
+        }
+        if let Some(summary) = self.summary.as_ref().filter(|_| self.config.summary) {
+            lines.push(summary.clone());
+        }
+
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+}
+
+impl<I: Iterator<Item = Result<String>>> Iterator for ContextTracker<I> {
+    // (行, 文脈)
+    type Item = Result<(String, Option<String>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(line) = self.pending.pop_front() {
+                let context = self.body_context(line.trim());
+                return Some(Ok((line, context)));
+            }
+
+            match self.lines.next() {
+                Some(Ok(line)) => {
+                    if let Some(context) = self.read(&line) {
+                        return Some(Ok((line, context)));
+                    }
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None if self.front_matter == FrontMatter::Inside => self.reopen_front_matter(),
+                None => return None,
+            }
+        }
+    }
+}
+
+// Markdownの ATX 見出し（"## 見出し"）
+pub(crate) fn parse_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let text = line[level..]
+        .strip_prefix(' ')?
+        .trim()
+        .trim_end_matches('#')
+        .trim();
+    (!text.is_empty()).then_some((level, text))
+}
+
+// 埋め込みに使うテキスト（文脈があれば前に付ける）
+pub fn embedding_text(context: Option<&str>, normalized: &str) -> String {
+    match context {
+        Some(context) => format!("{}\n{}", context, normalized),
+        None => normalized.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled() -> ContextConfig {
+        ContextConfig {
+            enabled: true,
+            ..ContextConfig::default()
+        }
+    }
+
+    // (行, 文脈) の組
+    fn contexts(config: &ContextConfig, text: &str) -> Vec<(String, Option<String>)> {
+        let lines = text.lines().map(|line| Ok(line.to_string()));
+        ContextTracker::new(lines, config, "notes.md")
+            .collect::<Result<_>>()
+            .unwrap()
+    }
+
+    fn pairs(expected: &[(&str, Option<&str>)]) -> Vec<(String, Option<String>)> {
+        expected
+            .iter()
+            .map(|(line, context)| (line.to_string(), context.map(str::to_string)))
+            .collect()
+    }
+
+    #[test]
+    fn front_matter_sets_the_title_and_summary() {
+        let text = "---\ntitle: \"Setup guide\"\ndescription: How to install\n---\nFirst line";
+        assert_eq!(
+            contexts(&enabled(), text),
+            pairs(&[("First line", Some("Setup guide\nHow to install"))])
+        );
+    }
+
+    #[test]
+    fn without_front_matter_the_file_name_is_the_title() {
+        let text = "First line\n---\ntitle: Not front matter";
+        assert_eq!(
+            contexts(&enabled(), text),
+            pairs(&[
+                ("First line", Some("notes.md")),
+                ("---", Some("notes.md")),
+                ("title: Not front matter", Some("notes.md")),
+            ])
+        );
+    }
+
+    #[test]
+    fn unterminated_front_matter_is_read_as_body_text() {
+        let text = "---\ntitle: Setup guide\n# Install\nRun the installer";
+        assert_eq!(
+            contexts(&enabled(), text),
+            pairs(&[
+                ("---", Some("notes.md")),
+                ("title: Setup guide", Some("notes.md")),
+                ("# Install", Some("notes.md")),
+                ("Run the installer", Some("notes.md > Install")),
+            ])
+        );
+    }
+
+    #[test]
+    fn a_higher_level_heading_closes_the_deeper_ones() {
+        let text = "# A\n## B\n### C\nc\n## D\nd\n# E\ne";
+        assert_eq!(
+            contexts(&enabled(), text),
+            pairs(&[
+                ("# A", Some("notes.md")),
+                ("## B", Some("notes.md > A")),
+                ("### C", Some("notes.md > A > B")),
+                ("c", Some("notes.md > A > B > C")),
+                ("## D", Some("notes.md > A")),
+                ("d", Some("notes.md > A > D")),
+                ("# E", Some("notes.md")),
+                ("e", Some("notes.md > E")),
+            ])
+        );
+    }
+
+    #[test]
+    fn disabled_context_keeps_every_line() {
+        let text = "---\ntitle: Setup guide\n---\n# Install";
+        assert_eq!(
+            contexts(&ContextConfig::default(), text),
+            pairs(&[
+                ("---", None),
+                ("title: Setup guide", None),
+                ("---", None),
+                ("# Install", None),
+            ])
+        );
+    }
+}
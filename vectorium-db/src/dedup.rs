@@ -0,0 +1,322 @@
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+// SimHashのシングル長（文字数）
+const SHINGLE_CHARS: usize = 3;
+
+// 重複排除の設定
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DedupConfig {
+    // 正規化した本文が完全一致する行を除外
+    pub exact: bool,
+    // SimHashによる類似行の除外
+    pub near: bool,
+    // 類似とみなすSimHashのハミング距離の上限
+    pub near_threshold: u32,
+    // 残すポイントに記録する重複元の最大件数
+    pub max_merged_locations: usize,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            exact: true,
+            near: false,
+            near_threshold: 3,
+            max_merged_locations: 100,
+        }
+    }
+}
+
+// 行の位置（ファイル番号, ファイル内通し番号）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Location {
+    pub file: usize,
+    pub seq: u64,
+}
+
+// 残したポイントにまとめた重複行
+#[derive(Debug, Default)]
+pub struct Merged {
+    pub count: u64,
+    pub locations: Vec<Location>,
+}
+
+// 重複排除の状態（実行中の全ファイルで共有）
+pub struct Deduplicator {
+    config: DedupConfig,
+    exact: HashMap<[u8; 16], Location>,
+    // SimHashとその行の位置
+    fingerprints: Vec<(u64, Location)>,
+    // バンドごとの値 -> fingerprints の添字
+    bands: Vec<HashMap<u64, Vec<usize>>>,
+    merged: HashMap<Location, Merged>,
+}
+
+impl Deduplicator {
+    pub fn new(config: &DedupConfig) -> Self {
+        // 距離 k 以内なら k+1 分割したバンドのいずれかが一致する
+        let band_count = if config.near {
+            (config.near_threshold as usize + 1).min(64)
+        } else {
+            0
+        };
+
+        Self {
+            config: config.clone(),
+            exact: HashMap::new(),
+            fingerprints: Vec::new(),
+            bands: vec![HashMap::new(); band_count],
+            merged: HashMap::new(),
+        }
+    }
+
+    // 重複なら残すポイントの位置を返し、重複元として記録する
+    pub fn check(&mut self, location: Location, text: &str) -> Option<Location> {
+        if !self.config.exact && !self.config.near {
+            return None;
+        }
+
+        let normalized = normalize(text);
+        let survivor = self.find(location, &normalized)?;
+
+        let merged = self.merged.entry(survivor).or_default();
+        merged.count += 1;
+        if merged.locations.len() < self.config.max_merged_locations {
+            merged.locations.push(location);
+        }
+        Some(survivor)
+    }
+
+    fn find(&mut self, location: Location, normalized: &str) -> Option<Location> {
+        let key = self.config.exact.then(|| {
+            let digest = Sha256::digest(normalized.as_bytes());
+            <[u8; 16]>::try_from(&digest[..16]).expect("digest is 32 bytes")
+        });
+        if let Some(survivor) = key.and_then(|key| self.exact.get(&key)) {
+            return Some(*survivor);
+        }
+
+        let mut survivor = None;
+        if self.config.near {
+            let fingerprint = simhash(normalized);
+            survivor = self.find_similar(fingerprint);
+            if survivor.is_none() {
+                self.insert_fingerprint(fingerprint, location);
+            }
+        }
+
+        // 類似行として除外した場合も、同じ本文は同じポイントへまとめる
+        if let Some(key) = key {
+            self.exact.insert(key, survivor.unwrap_or(location));
+        }
+        survivor
+    }
+
+    fn find_similar(&self, fingerprint: u64) -> Option<Location> {
+        let mut checked = HashSet::new();
+
+        for (band, index) in self.bands.iter().enumerate() {
+            let Some(candidates) = index.get(&self.band_value(fingerprint, band)) else {
+                continue;
+            };
+
+            for &candidate in candidates {
+                if !checked.insert(candidate) {
+                    continue;
+                }
+                let (other, survivor) = self.fingerprints[candidate];
+                if (fingerprint ^ other).count_ones() <= self.config.near_threshold {
+                    return Some(survivor);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn insert_fingerprint(&mut self, fingerprint: u64, location: Location) {
+        let index = self.fingerprints.len();
+        self.fingerprints.push((fingerprint, location));
+
+        for band in 0..self.bands.len() {
+            let value = self.band_value(fingerprint, band);
+            self.bands[band].entry(value).or_default().push(index);
+        }
+    }
+
+    // 64ビットをバンド数でほぼ均等に分割した band 番目の値
+    fn band_value(&self, fingerprint: u64, band: usize) -> u64 {
+        let count = self.bands.len();
+        let start = 64 * band / count;
+        let end = 64 * (band + 1) / count;
+        let width = end - start;
+
+        let mask = if width >= 64 {
+            u64::MAX
+        } else {
+            (1u64 << width) - 1
+        };
+        (fingerprint >> start) & mask
+    }
+
+    // 残したポイントごとの重複元
+    pub fn into_merged(self) -> HashMap<Location, Merged> {
+        self.merged
+    }
+}
+
+// テナントごとの重複排除（テナントをまたいで重複をまとめると他テナントから見えなくなるため分ける）
+pub struct TenantDeduplicators {
+    config: DedupConfig,
+    dedups: HashMap<String, Deduplicator>,
+}
+
+impl TenantDeduplicators {
+    pub fn new(config: &DedupConfig) -> Self {
+        Self {
+            config: config.clone(),
+            dedups: HashMap::new(),
+        }
+    }
+
+    // 同じテナント内で重複なら残すポイントの位置を返す
+    pub fn check(&mut self, tenant: &str, location: Location, text: &str) -> Option<Location> {
+        self.dedups
+            .entry(tenant.to_string())
+            .or_insert_with(|| Deduplicator::new(&self.config))
+            .check(location, text)
+    }
+
+    pub fn into_deduplicators(self) -> Vec<Deduplicator> {
+        self.dedups.into_values().collect()
+    }
+}
+
+// 空白の揺れを吸収した比較用テキスト
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// 文字単位のシングルから求めるSimHash（日本語のように空白で区切られない文にも対応）
+fn simhash(text: &str) -> u64 {
+    let chars: Vec<char> = text.chars().collect();
+    let mut weights = [0i64; 64];
+
+    let mut add = |shingle: &[char]| {
+        let mut hasher = DefaultHasher::new();
+        shingle.hash(&mut hasher);
+        let hash = hasher.finish();
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if hash >> bit & 1 == 1 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    };
+
+    if chars.len() <= SHINGLE_CHARS {
+        add(&chars);
+    } else {
+        chars.windows(SHINGLE_CHARS).for_each(&mut add);
+    }
+
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0, |fingerprint, (bit, _)| fingerprint | 1 << bit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGINAL: &str = "Vectorium splits documents into lines and embeds each line separately.";
+    const SIMILAR: &str = "Vectorium splits documents into lines and embeds each line separately!";
+
+    fn location(seq: u64) -> Location {
+        Location { file: 0, seq }
+    }
+
+    fn near(threshold: u32) -> DedupConfig {
+        DedupConfig {
+            exact: false,
+            near: true,
+            near_threshold: threshold,
+            ..DedupConfig::default()
+        }
+    }
+
+    fn distance(a: &str, b: &str) -> u32 {
+        (simhash(&normalize(a)) ^ simhash(&normalize(b))).count_ones()
+    }
+
+    #[test]
+    fn identical_lines_are_dropped() {
+        let mut dedup = Deduplicator::new(&DedupConfig::default());
+
+        assert_eq!(dedup.check(location(0), ORIGINAL), None);
+        // 空白の揺れは同じ本文として扱う
+        assert_eq!(
+            dedup.check(
+                location(1),
+                &format!("  {}  ", ORIGINAL.replace(' ', "   "))
+            ),
+            Some(location(0))
+        );
+        assert_eq!(dedup.check(location(2), ORIGINAL), Some(location(0)));
+        assert_eq!(dedup.check(location(3), SIMILAR), None);
+
+        let merged = dedup.into_merged();
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[&location(0)].count, 2);
+        assert_eq!(merged[&location(0)].locations, [location(1), location(2)]);
+    }
+
+    #[test]
+    fn near_duplicates_within_the_threshold_are_merged() {
+        let d = distance(ORIGINAL, SIMILAR);
+        assert!(d > 0, "test lines must differ in their SimHash");
+
+        for threshold in [d, d + 1, 63] {
+            let mut dedup = Deduplicator::new(&near(threshold));
+            assert_eq!(dedup.check(location(0), ORIGINAL), None);
+            assert_eq!(dedup.check(location(1), SIMILAR), Some(location(0)));
+        }
+    }
+
+    #[test]
+    fn near_duplicates_just_over_the_threshold_are_kept() {
+        let d = distance(ORIGINAL, SIMILAR);
+        assert!(d > 0, "test lines must differ in their SimHash");
+
+        let mut dedup = Deduplicator::new(&near(d - 1));
+        assert_eq!(dedup.check(location(0), ORIGINAL), None);
+        assert_eq!(dedup.check(location(1), SIMILAR), None);
+        // 残した行はそれぞれ以降の類似行の比較対象になる
+        assert_eq!(dedup.check(location(2), SIMILAR), Some(location(1)));
+    }
+
+    #[test]
+    fn same_text_in_two_tenants_is_kept_in_both() {
+        let mut dedups = TenantDeduplicators::new(&DedupConfig::default());
+
+        assert_eq!(dedups.check("acme", location(0), ORIGINAL), None);
+        assert_eq!(dedups.check("globex", location(1), ORIGINAL), None);
+        assert_eq!(
+            dedups.check("acme", location(2), ORIGINAL),
+            Some(location(0))
+        );
+        assert_eq!(
+            dedups.check("globex", location(3), ORIGINAL),
+            Some(location(1))
+        );
+        assert_eq!(dedups.into_deduplicators().len(), 2);
+    }
+}
@@ -0,0 +1,285 @@
+use anyhow::{Context, Result, anyhow, bail};
+use chardetng::EncodingDetector;
+use encoding_rs::{DecoderResult, EUC_JP, Encoding, ISO_2022_JP, SHIFT_JIS, UTF_8};
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+// 判定に使う先頭部分のサイズ
+const SAMPLE_BYTES: usize = 64 * 1024;
+// デコード時の入出力バッファサイズ
+const DECODE_BUFFER_BYTES: usize = 8 * 1024;
+
+// パスごとのエンコーディング指定
+#[derive(Debug, Clone, Deserialize)]
+pub struct EncodingOverride {
+    // globパターン
+    pub pattern: String,
+    pub encoding: String,
+}
+
+// 入力ファイルのエンコーディング設定
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EncodingConfig {
+    // "auto" の場合はBOMと内容から判定、それ以外はエンコーディング名
+    pub default: String,
+    // 自動判定が外れた場合に順に試すエンコーディング
+    pub fallbacks: Vec<String>,
+    pub overrides: Vec<EncodingOverride>,
+}
+
+impl Default for EncodingConfig {
+    fn default() -> Self {
+        Self {
+            default: "auto".to_string(),
+            fallbacks: [UTF_8, SHIFT_JIS, EUC_JP, ISO_2022_JP]
+                .iter()
+                .map(|encoding| encoding.name().to_string())
+                .collect(),
+            overrides: Vec::new(),
+        }
+    }
+}
+
+impl EncodingConfig {
+    // 指定されたエンコーディング（パスごとの指定を優先、自動判定なら None）
+    fn configured(&self, path: &Path) -> Result<Option<&'static Encoding>> {
+        for entry in &self.overrides {
+            let pattern = glob::Pattern::new(&entry.pattern)
+                .with_context(|| format!("Invalid encoding override pattern: {}", entry.pattern))?;
+            if pattern.matches_path(path) {
+                return lookup(&entry.encoding).map(Some);
+            }
+        }
+
+        match self.default.as_str() {
+            "auto" => Ok(None),
+            label => lookup(label).map(Some),
+        }
+    }
+
+    // ファイルのエンコーディングを判定し、全体をデコードできることを確認する
+    pub fn detect(&self, path: &Path) -> Result<&'static Encoding> {
+        let mut sample = Vec::with_capacity(SAMPLE_BYTES);
+        if self.configured(path)?.is_none() {
+            File::open(path)
+                .with_context(|| format!("Failed to open file: {}", path.display()))?
+                .take(SAMPLE_BYTES as u64)
+                .read_to_end(&mut sample)
+                .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        }
+
+        self.first_decodable(path, &sample, |encoding| {
+            validate(path, encoding).map(|()| encoding)
+        })
+    }
+
+    // ファイル以外（git のblobなど）の内容を、ファイルと同じ判定でデコードする（path は指定の照合とエラー表示用）
+    pub fn decode(&self, path: &Path, bytes: &[u8]) -> Result<String> {
+        let sample = &bytes[..bytes.len().min(SAMPLE_BYTES)];
+        self.first_decodable(path, sample, |encoding| decode_bytes(path, bytes, encoding))
+    }
+
+    // 指定、BOM、内容からの推定、フォールバックの順に試し、最初にデコードできた結果を返す
+    fn first_decodable<T>(
+        &self,
+        path: &Path,
+        sample: &[u8],
+        decode: impl Fn(&'static Encoding) -> Result<T>,
+    ) -> Result<T> {
+        if let Some(encoding) = self.configured(path)? {
+            return decode(encoding);
+        }
+
+        // BOMがあればそれに従う
+        if let Some((encoding, _)) = Encoding::for_bom(sample) {
+            return decode(encoding);
+        }
+
+        let mut candidates = Vec::new();
+        // ISO-2022-JPは7ビットのためUTF-8としても妥当になる。エスケープシーケンスで先に判定
+        if has_iso_2022_jp_escape(sample) {
+            candidates.push(ISO_2022_JP);
+        }
+        let mut detector = EncodingDetector::new();
+        detector.feed(sample, sample.len() < SAMPLE_BYTES);
+        candidates.push(detector.guess(Some(b"jp"), true));
+        for label in &self.fallbacks {
+            candidates.push(lookup(label)?);
+        }
+
+        let mut tried: Vec<&'static Encoding> = Vec::new();
+        for encoding in candidates {
+            if tried.contains(&encoding) {
+                continue;
+            }
+            tried.push(encoding);
+            if let Ok(decoded) = decode(encoding) {
+                return Ok(decoded);
+            }
+        }
+
+        let names = tried
+            .iter()
+            .map(|encoding| encoding.name())
+            .collect::<Vec<_>>()
+            .join(", ");
+        bail!("Could not decode {} as any of: {}", path.display(), names)
+    }
+}
+
+fn lookup(label: &str) -> Result<&'static Encoding> {
+    Encoding::for_label(label.as_bytes()).ok_or_else(|| anyhow!("Unknown encoding: {}", label))
+}
+
+fn has_iso_2022_jp_escape(sample: &[u8]) -> bool {
+    sample
+        .windows(3)
+        .any(|window| matches!(window, b"\x1b$B" | b"\x1b$@" | b"\x1b(J"))
+}
+
+// ファイル全体を置換なしでデコードできるか検査
+fn validate(path: &Path, encoding: &'static Encoding) -> Result<()> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+    io::copy(&mut DecodingReader::new(file, encoding), &mut io::sink())
+        .with_context(|| format!("Failed to decode {} as {}", path.display(), encoding.name()))?;
+    Ok(())
+}
+
+// メモリ上の内容を置換なしでデコード（先頭のBOMは除く）
+fn decode_bytes(path: &Path, bytes: &[u8], encoding: &'static Encoding) -> Result<String> {
+    let bytes = match Encoding::for_bom(bytes) {
+        Some((bom, length)) if bom == encoding => &bytes[length..],
+        _ => bytes,
+    };
+    encoding
+        .decode_without_bom_handling_and_without_replacement(bytes)
+        .map(|text| text.into_owned())
+        .ok_or_else(|| anyhow!("Failed to decode {} as {}", path.display(), encoding.name()))
+}
+
+// 指定エンコーディングからUTF-8へ変換するリーダー（不正なバイト列はエラー）
+pub struct DecodingReader<R> {
+    inner: R,
+    encoding: &'static Encoding,
+    decoder: encoding_rs::Decoder,
+    input: Vec<u8>,
+    input_pos: usize,
+    output: Vec<u8>,
+    output_pos: usize,
+    // 入力の総読み込みバイト数（エラー位置の報告用）
+    consumed: u64,
+    eof: bool,
+    finished: bool,
+}
+
+impl<R: Read> DecodingReader<R> {
+    pub fn new(inner: R, encoding: &'static Encoding) -> Self {
+        Self {
+            inner,
+            encoding,
+            decoder: encoding.new_decoder_with_bom_removal(),
+            input: Vec::new(),
+            input_pos: 0,
+            output: Vec::new(),
+            output_pos: 0,
+            consumed: 0,
+            eof: false,
+            finished: false,
+        }
+    }
+
+    fn fill_input(&mut self) -> io::Result<()> {
+        self.input.resize(DECODE_BUFFER_BYTES, 0);
+        let read = self.inner.read(&mut self.input)?;
+        self.input.truncate(read);
+        self.input_pos = 0;
+        self.eof = read == 0;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.output_pos < self.output.len() {
+                let count = buf.len().min(self.output.len() - self.output_pos);
+                buf[..count]
+                    .copy_from_slice(&self.output[self.output_pos..self.output_pos + count]);
+                self.output_pos += count;
+                return Ok(count);
+            }
+            if self.finished {
+                return Ok(0);
+            }
+
+            if self.input_pos == self.input.len() && !self.eof {
+                self.fill_input()?;
+            }
+
+            self.output.resize(DECODE_BUFFER_BYTES, 0);
+            self.output_pos = 0;
+            let (result, read, written) = self.decoder.decode_to_utf8_without_replacement(
+                &self.input[self.input_pos..],
+                &mut self.output,
+                self.eof,
+            );
+            self.input_pos += read;
+            self.consumed += read as u64;
+            self.output.truncate(written);
+
+            match result {
+                DecoderResult::Malformed(_, _) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "Malformed {} sequence near byte {}",
+                            self.encoding.name(),
+                            self.consumed
+                        ),
+                    ));
+                }
+                DecoderResult::InputEmpty if self.eof => self.finished = true,
+                DecoderResult::InputEmpty | DecoderResult::OutputFull => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_detects_japanese_encodings_in_memory() {
+        let config = EncodingConfig::default();
+        let text = "日本語のテキストです。文字コードを判定します。";
+        for encoding in [UTF_8, SHIFT_JIS, EUC_JP, ISO_2022_JP] {
+            let (bytes, _, _) = encoding.encode(text);
+            let decoded = config.decode(Path::new("docs/readme.txt"), &bytes).unwrap();
+            assert_eq!(decoded, text, "{}", encoding.name());
+        }
+    }
+
+    #[test]
+    fn decode_follows_overrides_and_strips_the_bom() {
+        let config = EncodingConfig {
+            overrides: vec![EncodingOverride {
+                pattern: "legacy/*".to_string(),
+                encoding: "shift_jis".to_string(),
+            }],
+            ..EncodingConfig::default()
+        };
+        let (bytes, _, _) = SHIFT_JIS.encode("設定");
+        assert_eq!(
+            config.decode(Path::new("legacy/a.txt"), &bytes).unwrap(),
+            "設定"
+        );
+
+        let bytes = [b"\xEF\xBB\xBF".as_slice(), "本文".as_bytes()].concat();
+        assert_eq!(config.decode(Path::new("a.txt"), &bytes).unwrap(), "本文");
+    }
+}
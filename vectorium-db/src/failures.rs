@@ -0,0 +1,292 @@
+use anyhow::{Context, Result};
+use qdrant_client::Qdrant;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::atomic;
+use crate::collections;
+use crate::config::Config;
+use crate::parents::Section;
+use crate::pipeline::{self, Line, Neighbors};
+use crate::vectors::Embedders;
+
+// チャンクの埋め込み・upsertに失敗したときの扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailurePolicy {
+    // 実行全体を中止
+    Abort,
+    // デッドレターに記録して続行
+    Skip,
+    // 再試行し、それでも失敗したら記録して続行
+    RetryThenSkip,
+}
+
+// 失敗時の設定
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FailureConfig {
+    pub policy: FailurePolicy,
+    // retry-then-skip の再試行回数
+    pub retries: u32,
+    // 再試行の初回待ち時間（回数ごとに倍増）
+    pub retry_delay_ms: u64,
+    pub dead_letter_path: PathBuf,
+}
+
+impl Default for FailureConfig {
+    fn default() -> Self {
+        Self {
+            policy: FailurePolicy::Abort,
+            retries: 3,
+            retry_delay_ms: 1000,
+            dead_letter_path: PathBuf::from("vectorium-dead-letter.jsonl"),
+        }
+    }
+}
+
+impl FailureConfig {
+    // ポリシーに従って再試行する（最終的なエラーを返す）
+    pub async fn retry<T, F, Fut>(&self, mut operation: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match operation().await {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt >= self.retries() => return Err(e),
+                Err(_) => {
+                    self.backoff(attempt).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    // 失敗後に再試行する回数
+    pub fn retries(&self) -> u32 {
+        match self.policy {
+            FailurePolicy::RetryThenSkip => self.retries,
+            FailurePolicy::Abort | FailurePolicy::Skip => 0,
+        }
+    }
+
+    // attempt 回目の失敗後、再試行まで待つ
+    pub async fn backoff(&self, attempt: u32) {
+        let delay = self.retry_delay_ms.saturating_mul(1 << attempt.min(16));
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+    }
+
+    pub fn aborts(&self) -> bool {
+        self.policy == FailurePolicy::Abort
+    }
+}
+
+// 失敗したチャンク（replay で再処理できるよう原文と位置を保持）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    // 失敗したステージ（embed / upsert）
+    pub stage: String,
+    pub collection: String,
+    pub source: String,
+    // 未記録（テナント導入前）の場合は default_tenant
+    #[serde(default)]
+    pub tenant: Option<String>,
+    pub title: String,
+    pub chunk: u64,
+    pub seqs: Vec<u64>,
+    pub lines: Vec<String>,
+    // 埋め込み時に付けた文脈（行ごと、文脈を付けていない場合は空）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contexts: Vec<Option<String>>,
+    // 親セクションの参照（行ごと、親子チャンクが無効な場合は空）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parents: Vec<Option<DeadLetterParent>>,
+    // 前後の行の番号（行ごと、未記録の場合は空）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub neighbors: Vec<Neighbors>,
+    pub error: String,
+    pub failed_at: String,
+}
+
+// 行が参照する親セクション（本文は本文を持つポイントの分のみ）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetterParent {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+impl DeadLetter {
+    pub fn load_all(path: &Path) -> Result<Vec<Self>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to open dead letters: {}", path.display()));
+            }
+        };
+
+        BufReader::new(file)
+            .lines()
+            .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .map(|line| serde_json::from_str(&line?).context("Failed to parse dead letter entry"))
+            .collect()
+    }
+
+    // 残った失敗で置き換える（なければファイルを削除）
+    pub fn save_all(path: &Path, entries: &[Self]) -> Result<()> {
+        if entries.is_empty() {
+            return match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e)
+                    .with_context(|| format!("Failed to remove dead letters: {}", path.display())),
+                _ => Ok(()),
+            };
+        }
+
+        let mut content = Vec::new();
+        for entry in entries {
+            serde_json::to_writer(&mut content, entry)?;
+            content.push(b'\n');
+        }
+        atomic::write(path, &content, "dead letters")
+    }
+}
+
+// デッドレターの追記先（最初の失敗時に開き、各エントリを書き込み後にフラッシュ）
+pub struct DeadLetterWriter {
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl DeadLetterWriter {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(None),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&self, entry: &DeadLetter) -> Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock().expect("dead letter file poisoned");
+        if file.is_none() {
+            let opened = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .with_context(|| format!("Failed to open dead letters: {}", self.path.display()))?;
+            *file = Some(opened);
+        }
+
+        let file = file.as_mut().expect("dead letter file opened");
+        file.write_all(&line)
+            .and_then(|_| file.flush())
+            .with_context(|| format!("Failed to write dead letter: {}", self.path.display()))
+    }
+}
+
+// 再処理の結果
+pub struct Replayed {
+    pub chunks: usize,
+    pub points: u64,
+    pub remaining: usize,
+}
+
+// デッドレターのチャンクを埋め込み直してupsertし、失敗したものだけを残す
+pub async fn replay(
+    client: &Qdrant,
+    config: &Config,
+    embedders: &Embedders,
+    path: &Path,
+) -> Result<Replayed> {
+    let entries = DeadLetter::load_all(path)?;
+    let chunks = entries.len();
+    let mut points = 0u64;
+    let mut remaining = Vec::new();
+
+    for mut entry in entries {
+        match replay_entry(client, config, embedders, &entry).await {
+            Ok(count) => points += count,
+            Err(e) => {
+                eprintln!(
+                    "Failed to replay {} chunk {}: {:#}",
+                    entry.source, entry.chunk, e
+                );
+                entry.error = format!("{:#}", e);
+                entry.failed_at = chrono::Utc::now().to_rfc3339();
+                remaining.push(entry);
+            }
+        }
+    }
+
+    DeadLetter::save_all(path, &remaining)?;
+    Ok(Replayed {
+        chunks,
+        points,
+        remaining: remaining.len(),
+    })
+}
+
+async fn replay_entry(
+    client: &Qdrant,
+    config: &Config,
+    embedders: &Embedders,
+    entry: &DeadLetter,
+) -> Result<u64> {
+    // 失敗時のコレクションが削除済みなら公開中のコレクションへ投入
+    let collection_name = if client.collection_exists(&entry.collection).await? {
+        entry.collection.clone()
+    } else {
+        collections::resolve(client, &config.collection)
+            .await?
+            .with_context(|| format!("Collection {} does not exist", config.collection))?
+    };
+
+    let lines: Vec<Line> = entry
+        .lines
+        .iter()
+        .enumerate()
+        .map(|(i, text)| Line {
+            text: text.clone(),
+            normalized: config.normalize.apply(text),
+            context: entry.contexts.get(i).cloned().flatten(),
+            section: entry
+                .parents
+                .get(i)
+                .cloned()
+                .flatten()
+                .map(|parent| Arc::new(Section::restore(parent.id, parent.text))),
+            neighbors: entry.neighbors.get(i).copied().unwrap_or_default(),
+        })
+        .collect();
+
+    let result = pipeline::process_chunk(
+        embedders,
+        &lines,
+        &entry.seqs,
+        &entry.source,
+        entry.tenant.as_deref().unwrap_or(&config.default_tenant),
+        &entry.title,
+        &config.schema,
+    )
+    .await?;
+    let count = result.points.len() as u64;
+    pipeline::upsert_points(client, &collection_name, result.points, &config.processing).await?;
+
+    Ok(count)
+}
@@ -0,0 +1,320 @@
+use anyhow::{Context, Result, bail};
+use qdrant_client::Payload;
+use qdrant_client::qdrant::{Condition, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+use crate::atomic;
+use crate::text::{Passage, TextIngester};
+
+// コミットログの区切り（フィールド区切りとレコード区切り）
+const FIELD_SEPARATOR: char = '\u{1f}';
+const RECORD_SEPARATOR: char = '\u{1e}';
+
+// gitリポジトリ読み込みの設定
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GitConfig {
+    // 最後に投入したコミットの記録先（差分投入用）
+    pub state_path: PathBuf,
+    // --files-at で投入するファイルのパターン（リポジトリ内のパス）
+    pub file_patterns: Vec<String>,
+}
+
+impl Default for GitConfig {
+    fn default() -> Self {
+        Self {
+            state_path: PathBuf::from("vectorium-git-state.json"),
+            file_patterns: vec!["*.md".to_string(), "*.txt".to_string()],
+        }
+    }
+}
+
+// リポジトリごとの投入済み位置
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct RepoState {
+    // 投入先のコレクション（別のバージョンに切り替わっていれば全件投入し直す）
+    collection: String,
+    // 最後に投入したHEAD
+    head: Option<String>,
+    // ref -> 投入したファイルのコミット
+    refs: BTreeMap<String, String>,
+}
+
+// 永続化する状態（キーはテナントとリポジトリのパス）
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct GitState {
+    repos: BTreeMap<String, RepoState>,
+}
+
+impl GitState {
+    fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read git state: {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse git state: {}", path.display()))
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_vec_pretty(self).context("Failed to serialize git state")?;
+        atomic::write(path, &content, "git state")
+    }
+}
+
+// 投入結果
+#[derive(Debug, Default)]
+pub struct GitIngested {
+    pub commits: u64,
+    pub files: u64,
+    // UTF-8でないため飛ばしたファイル
+    pub skipped_files: u64,
+    pub points: u64,
+    // 前回の位置から差分のみ投入したか
+    pub incremental: bool,
+}
+
+// 1コミット分のログ
+struct Commit {
+    hash: String,
+    author: String,
+    author_email: String,
+    date: String,
+    message: String,
+}
+
+// ローカルのリポジトリでgitを実行し、標準出力を返す（ネットワークには接続しない）
+async fn git(repo: &Path, args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .await
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+async fn git_text(repo: &Path, args: &[&str]) -> Result<String> {
+    String::from_utf8(git(repo, args).await?).context("git output is not valid UTF-8")
+}
+
+async fn rev_parse(repo: &Path, rev: &str) -> Result<String> {
+    let commit = format!("{}^{{commit}}", rev);
+    Ok(git_text(repo, &["rev-parse", "--verify", &commit])
+        .await?
+        .trim()
+        .to_string())
+}
+
+// ancestor から head まで履歴がつながっているか（履歴の書き換えがないか）
+async fn is_ancestor(repo: &Path, ancestor: &str, head: &str) -> bool {
+    git(repo, &["merge-base", "--is-ancestor", ancestor, head])
+        .await
+        .is_ok()
+}
+
+async fn read_commits(repo: &Path, range: &str) -> Result<Vec<Commit>> {
+    let format = format!(
+        "--format=%H{0}%an{0}%ae{0}%aI{0}%B{1}",
+        FIELD_SEPARATOR, RECORD_SEPARATOR
+    );
+    let log = git_text(repo, &["log", &format, range]).await?;
+
+    log.split(RECORD_SEPARATOR)
+        .map(str::trim)
+        .filter(|record| !record.is_empty())
+        .map(|record| {
+            let fields: Vec<&str> = record.splitn(5, FIELD_SEPARATOR).collect();
+            let [hash, author, author_email, date, message] = fields[..] else {
+                bail!("Unexpected git log record: {}", record);
+            };
+            Ok(Commit {
+                hash: hash.to_string(),
+                author: author.to_string(),
+                author_email: author_email.to_string(),
+                date: date.to_string(),
+                message: message.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+// コミットハッシュの先頭64ビット（ソース内で安定したポイント番号、順序の意味はない）
+fn commit_seq(hash: &str) -> Result<u64> {
+    hash.get(..16)
+        .and_then(|prefix| u64::from_str_radix(prefix, 16).ok())
+        .with_context(|| format!("Invalid commit hash: {}", hash))
+}
+
+fn commit_passage(repository: &str, commit: Commit) -> Result<Passage> {
+    let metadata = [
+        ("repository", repository.to_string()),
+        ("kind", "commit".to_string()),
+        ("commit", commit.hash.clone()),
+        ("author", commit.author),
+        ("author_email", commit.author_email),
+        ("date", commit.date),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), Value::from(value)))
+    .collect::<HashMap<_, _>>();
+
+    Ok(Passage {
+        seq: commit_seq(&commit.hash)?,
+        text: commit.message,
+        metadata,
+    })
+}
+
+// コミットメッセージと、指定したrefの時点のファイルを公開中のコレクションへ投入
+// 前回と同じコレクションで履歴がつながっていれば、前回のHEAD以降のコミットだけを投入する
+pub async fn ingest(
+    ingester: &TextIngester,
+    config: &GitConfig,
+    repo: &Path,
+    files_at: &[String],
+    full: bool,
+) -> Result<GitIngested> {
+    let repo = repo
+        .canonicalize()
+        .with_context(|| format!("Repository not found: {}", repo.display()))?;
+    let repository = repo.display().to_string();
+    let title = repo
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| repository.clone());
+    let source = format!("git:{}", repository);
+
+    let collection_name = ingester.collection().await?;
+    let mut state = GitState::load(&config.state_path)?;
+    let key = format!("{}@{}", ingester.tenant(), repository);
+    let previous = state
+        .repos
+        .get(&key)
+        .filter(|previous| !full && previous.collection == collection_name)
+        .cloned()
+        .unwrap_or_default();
+    let mut current = RepoState {
+        collection: collection_name.clone(),
+        ..previous.clone()
+    };
+    let mut result = GitIngested::default();
+
+    // コミットメッセージ
+    let head = rev_parse(&repo, "HEAD").await?;
+    let range = match &previous.head {
+        Some(last) if is_ancestor(&repo, last, &head).await => {
+            result.incremental = true;
+            Some(format!("{}..{}", last, head))
+        }
+        _ => None,
+    };
+    if previous.head.as_deref() != Some(head.as_str()) {
+        let commits = read_commits(&repo, range.as_deref().unwrap_or(&head)).await?;
+        let passages = commits
+            .into_iter()
+            .filter(|commit| !commit.message.is_empty())
+            .map(|commit| commit_passage(&repository, commit))
+            .collect::<Result<Vec<_>>>()?;
+
+        // 全件投入では書き換えられた履歴のコミットが残らないよう先に削除
+        if range.is_none() {
+            ingester
+                .delete(
+                    &collection_name,
+                    vec![Condition::matches("source", source.clone())],
+                )
+                .await?;
+        }
+        let processing = &ingester.config().processing;
+        let batch_size = (processing.batch_size * processing.chunk_size).max(1);
+        for batch in passages.chunks(batch_size) {
+            // seq はハッシュ由来で履歴順ではないため、前後のコミットとはつながない
+            let points = ingester.embed_unlinked(&source, &title, batch).await?;
+            result.points += points.len() as u64;
+            ingester.upsert(&collection_name, points).await?;
+        }
+        result.commits = passages.len() as u64;
+    }
+    current.head = Some(head);
+    state.repos.insert(key.clone(), current.clone());
+    state.save(&config.state_path)?;
+
+    // 指定したrefの時点のファイル
+    let patterns = config
+        .file_patterns
+        .iter()
+        .map(|pattern| {
+            glob::Pattern::new(pattern)
+                .with_context(|| format!("Invalid git file pattern: {}", pattern))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for git_ref in files_at {
+        let commit = rev_parse(&repo, git_ref).await?;
+        if previous.refs.get(git_ref) == Some(&commit) {
+            continue;
+        }
+
+        // 前回その ref で投入し、今は存在しないファイルを残さない
+        ingester
+            .delete(
+                &collection_name,
+                vec![
+                    Condition::matches("repository", repository.clone()),
+                    Condition::matches("git_ref", git_ref.clone()),
+                ],
+            )
+            .await?;
+
+        let listing = git_text(&repo, &["ls-tree", "-r", "--name-only", "-z", &commit]).await?;
+        for path in listing
+            .split('\0')
+            .filter(|path| patterns.iter().any(|pattern| pattern.matches(path)))
+        {
+            let content = git(&repo, &["show", &format!("{}:{}", commit, path)]).await?;
+            // 作業ツリーのファイルと同じエンコーディング判定でデコードする
+            let Ok(text) = ingester.config().encoding.decode(Path::new(path), &content) else {
+                result.skipped_files += 1;
+                continue;
+            };
+
+            let metadata: Payload = [
+                ("repository", Value::from(repository.clone())),
+                ("kind", Value::from("file")),
+                ("git_ref", Value::from(git_ref.clone())),
+                ("commit", Value::from(commit.clone())),
+                ("path", Value::from(path)),
+            ]
+            .into();
+            let ingested = ingester
+                .ingest_document(
+                    &format!("git:{}@{}:{}", repository, git_ref, path),
+                    path,
+                    &text,
+                    metadata,
+                )
+                .await?;
+            result.files += 1;
+            result.points += ingested.points;
+        }
+
+        current.refs.insert(git_ref.clone(), commit);
+        state.repos.insert(key.clone(), current.clone());
+        state.save(&config.state_path)?;
+    }
+
+    Ok(result)
+}
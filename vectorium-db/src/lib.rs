@@ -0,0 +1,26 @@
+// インジェスターの各処理（コマンドラインは main.rs、他のツールからは ingest_text で投入）
+pub mod atomic;
+pub mod chat;
+pub mod checkpoint;
+pub mod collections;
+pub mod config;
+pub mod context;
+pub mod dedup;
+pub mod encoding;
+pub mod failures;
+pub mod git;
+pub mod mail;
+pub mod migrate;
+pub mod normalize;
+pub mod parents;
+pub mod pipeline;
+pub mod plan;
+pub mod progress;
+pub mod report;
+pub mod schema;
+pub mod text;
+pub mod transfer;
+pub mod tuning;
+pub mod vectors;
+
+pub use text::{IngestedText, Passage, TextIngester, ingest_text};
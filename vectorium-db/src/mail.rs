@@ -0,0 +1,265 @@
+use anyhow::{Context, Result};
+use mail_parser::{Address, HeaderValue, Message, MessageParser, mailbox};
+use qdrant_client::qdrant::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+
+use crate::text::{Passage, TextIngester};
+
+// この行以降は引用された元のメール
+const ORIGINAL_MESSAGE_MARKERS: [&str; 2] =
+    ["-----Original Message-----", "-----元のメッセージ-----"];
+// 引用の前に付く「〜 wrote:」の行
+const ATTRIBUTION_SUFFIXES: [&str; 3] = ["wrote:", "書きました:", "書きました："];
+// 署名の区切り
+const SIGNATURE_DELIMITER: &str = "-- ";
+
+// 投入結果
+#[derive(Debug, Default)]
+pub struct MailIngested {
+    pub messages: u64,
+    // 解析できない、または引用を除くと本文が空のメッセージ
+    pub skipped: u64,
+    pub points: u64,
+}
+
+// mbox ファイルか Maildir ディレクトリ（cur/new を持つ）のメッセージを順に読む
+fn read_mailbox(path: &Path) -> Result<Box<dyn Iterator<Item = io::Result<Vec<u8>>> + Send>> {
+    if path.is_dir() {
+        let messages = mailbox::maildir::MessageIterator::new(path)
+            .with_context(|| format!("Failed to open Maildir: {}", path.display()))?;
+        Ok(Box::new(messages.map(|message| {
+            message.map(|message| message.unwrap_contents())
+        })))
+    } else {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open mbox: {}", path.display()))?;
+        let messages = mailbox::mbox::MessageIterator::new(BufReader::new(file));
+        Ok(Box::new(messages.map(|message| {
+            message.map(|message| message.unwrap_contents())
+        })))
+    }
+}
+
+// 返信で引用された部分と署名を除く
+fn strip_quoted(body: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+
+    for line in body.lines() {
+        let trimmed = line.trim();
+        if line == SIGNATURE_DELIMITER
+            || ORIGINAL_MESSAGE_MARKERS
+                .iter()
+                .any(|marker| trimmed.starts_with(marker))
+        {
+            break;
+        }
+        if trimmed.starts_with('>') {
+            continue;
+        }
+        lines.push(line.trim_end());
+    }
+
+    // 引用の直前にある「〜 wrote:」の行も除く
+    while let Some(last) = lines.iter().rposition(|line| !line.trim().is_empty()) {
+        let last_line = lines[last].trim();
+        if !ATTRIBUTION_SUFFIXES
+            .iter()
+            .any(|suffix| last_line.ends_with(suffix))
+        {
+            break;
+        }
+        lines.truncate(last);
+    }
+
+    lines.join("\n")
+}
+
+// 空行で区切った段落（長いメールでも埋め込みモデルの入力長に収まりやすくする）
+fn paragraphs(body: &str) -> Vec<String> {
+    body.split("\n\n")
+        .map(str::trim)
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn addresses(address: Option<&Address>) -> Vec<String> {
+    address
+        .map(|address| {
+            address
+                .iter()
+                .filter_map(|addr| addr.address())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// スレッドの起点のメッセージID（References の先頭、なければ返信先、なければ自身）
+fn thread_id(message: &Message, message_id: &str) -> String {
+    let first = |value: &HeaderValue| {
+        value
+            .as_text_list()
+            .and_then(|ids| ids.first())
+            .map(|id| id.to_string())
+    };
+    first(message.references())
+        .or_else(|| first(message.in_reply_to()))
+        .unwrap_or_else(|| message_id.to_string())
+}
+
+// 1通分のパッセージ（段落ごと）
+fn message_passages(mailbox: &str, message: &Message, message_id: &str) -> Vec<Passage> {
+    // text/plain がなければ HTML をテキストに変換した本文になる
+    let body = (0..message.text_body_count())
+        .filter_map(|pos| message.body_text(pos))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let from = addresses(message.from());
+    let mut metadata: HashMap<String, Value> = [
+        ("kind", Value::from("mail")),
+        ("mailbox", Value::from(mailbox)),
+        ("message_id", Value::from(message_id)),
+        ("thread_id", Value::from(thread_id(message, message_id))),
+        ("to", Value::from(addresses(message.to()))),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), value))
+    .collect();
+    if let Some(from) = from.first() {
+        metadata.insert("from".to_string(), from.clone().into());
+    }
+    if let Some(subject) = message.subject() {
+        metadata.insert("subject".to_string(), subject.into());
+    }
+    if let Some(date) = message.date() {
+        metadata.insert("date".to_string(), date.to_rfc3339().into());
+    }
+
+    paragraphs(&strip_quoted(&body))
+        .into_iter()
+        .enumerate()
+        .map(|(seq, text)| Passage {
+            seq: seq as u64,
+            text,
+            metadata: metadata.clone(),
+        })
+        .collect()
+}
+
+// メールボックスの各メッセージを段落ごとに公開中のコレクションへ投入
+// メッセージIDごとにポイントIDが決まるため、同じメールボックスを再投入しても重複しない
+pub async fn ingest(ingester: &TextIngester, path: &Path) -> Result<MailIngested> {
+    let collection_name = ingester.collection().await?;
+    let mailbox = path.display().to_string();
+    let parser = MessageParser::default();
+    let mut result = MailIngested::default();
+
+    for raw in read_mailbox(path)? {
+        let raw = raw.with_context(|| format!("Failed to read mailbox: {}", mailbox))?;
+        let Some(message) = parser.parse(&raw) else {
+            result.skipped += 1;
+            continue;
+        };
+
+        // Message-ID がないメッセージは内容から識別子を作る
+        let message_id = match message.message_id() {
+            Some(id) => id.to_string(),
+            None => format!("sha256:{:x}", Sha256::digest(&raw)),
+        };
+        let passages = message_passages(&mailbox, &message, &message_id);
+        if passages.is_empty() {
+            result.skipped += 1;
+            continue;
+        }
+
+        let title = message.subject().unwrap_or("(no subject)");
+        let points = ingester
+            .embed(&format!("mail:{}", message_id), title, &passages)
+            .await?;
+        result.points += points.len() as u64;
+        ingester.upsert(&collection_name, points).await?;
+        result.messages += 1;
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_quoted_keeps_only_the_new_text() {
+        let cases = [
+            // 引用なし
+            ("Hello\n\nSee you", "Hello\n\nSee you"),
+            // 「>」の引用行
+            ("Sounds good.\n> Shall we meet?\n>> Earlier", "Sounds good."),
+            ("Inline reply\n  > quoted\nafter", "Inline reply\nafter"),
+            // 引用の前の「On ... wrote:」の行
+            (
+                "Agreed.\n\nOn Mon, 1 Jan 2024 at 10:00, Alice <alice@example.com> wrote:\n> Proposal",
+                "Agreed.",
+            ),
+            (
+                "了解です。\n\n2024年1月1日 10:00 山田 <yamada@example.com> 書きました:\n> 提案",
+                "了解です。",
+            ),
+            // 「wrote:」で終わらない行は残す
+            ("He wrote: hello\n> quoted", "He wrote: hello"),
+            // 署名と元のメッセージ以降
+            ("Thanks\n-- \nAlice\nExample Inc.", "Thanks"),
+            ("Thanks\n--\nAlice", "Thanks\n--\nAlice"),
+            (
+                "See below\n-----Original Message-----\nFrom: Bob\nOld body",
+                "See below",
+            ),
+            ("確認します\n  -----元のメッセージ-----\n本文", "確認します"),
+        ];
+
+        for (body, expected) in cases {
+            // 末尾の空行は段落に分けるときに除かれる
+            assert_eq!(strip_quoted(body).trim_end(), expected, "body: {:?}", body);
+        }
+    }
+
+    #[test]
+    fn thread_id_falls_back_from_references_to_in_reply_to() {
+        let cases = [
+            // References の先頭がスレッドの起点
+            (
+                "References: <root@example.com> <parent@example.com>\r\nIn-Reply-To: <parent@example.com>\r\n",
+                "root@example.com",
+            ),
+            ("References: <root@example.com>\r\n", "root@example.com"),
+            // References がなければ返信先
+            (
+                "In-Reply-To: <parent@example.com>\r\n",
+                "parent@example.com",
+            ),
+            // どちらもなければ自身
+            ("", "self@example.com"),
+        ];
+
+        for (headers, expected) in cases {
+            let raw = format!(
+                "Message-ID: <self@example.com>\r\nSubject: Test\r\n{}\r\nBody\r\n",
+                headers
+            );
+            let message = MessageParser::default().parse(raw.as_bytes()).unwrap();
+            let message_id = message.message_id().unwrap();
+            assert_eq!(
+                thread_id(&message, message_id),
+                expected,
+                "headers: {:?}",
+                headers
+            );
+        }
+    }
+}
@@ -1,221 +1,506 @@
 use anyhow::{Context, Result};
+use clap::Parser;
 use glob::glob;
-use qdrant_client::qdrant::{
-    CreateCollectionBuilder, Distance, PointStruct, UpsertPointsBuilder, VectorParamsBuilder,
-};
+use qdrant_client::Qdrant;
+use qdrant_client::qdrant::Value;
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::AsyncReadExt;
 
-use vectorium_common::get_embedding;
 use vectorium_common::get_qdrant_client;
 
-// 設定構造体でマジックナンバーを排除
-#[derive(Debug, Clone)]
-struct ProcessingConfig {
-    chunk_size: usize,
-    batch_size: usize,
-    buffer_size: usize,
-}
+mod cli;
+
+use cli::{Cli, Command, IngestArgs};
+use vectorium_db::checkpoint::{Checkpoint, CheckpointTracker};
+use vectorium_db::config::Config;
+use vectorium_db::failures::{self, DeadLetter, DeadLetterWriter};
+use vectorium_db::pipeline::{self, Pipeline, PipelineStats};
+use vectorium_db::progress::{OutputMode, Progress};
+use vectorium_db::report::{IngestReport, ModelReport, RunStatus};
+use vectorium_db::vectors::start_embedders;
+use vectorium_db::{
+    TextIngester, chat, collections, git, mail, migrate, plan, text, transfer, tuning,
+};
 
-impl Default for ProcessingConfig {
-    fn default() -> Self {
-        Self {
-            chunk_size: 3000,
-            batch_size: 5,
-            buffer_size: 64 * 1024,
+// ファイルパターンからファイルリストを取得（ファイルと所属テナント）
+fn collect_files(config: &Config) -> Result<Vec<(PathBuf, String)>> {
+    let tenants = std::iter::once((&config.default_tenant, &config.sources)).chain(
+        config
+            .tenants
+            .iter()
+            .map(|tenant| (&tenant.id, &tenant.sources)),
+    );
+
+    let mut files = Vec::new();
+    for (tenant, patterns) in tenants {
+        for pattern in patterns {
+            for path in glob(pattern).context("Failed to read glob pattern")? {
+                let path = path.context("Failed to collect file paths")?;
+                files.push((path, tenant.clone()));
+            }
         }
     }
+    Ok(files)
 }
 
-// ファイル処理の結果
-struct ProcessingResult {
-    total_points: u64,
-    points: Vec<PointStruct>,
-}
-
-// チャンク処理（関数型スタイル）
-async fn process_chunk(chunk: &[String], start_id: u64, title: &str) -> Result<ProcessingResult> {
-    println!("Generating embeddings for {} sentences...", chunk.len());
-
-    let embeddings = get_embedding(chunk.to_vec()).await;
-
-    let points: Vec<PointStruct> = embeddings
-        .into_iter()
-        .zip(chunk.iter())
-        .enumerate()
-        .map(|(i, (embedding, sentence))| {
-            let point_id = start_id + i as u64 + 1;
-
-            let payload = [
-                ("title".to_string(), title.to_string().into()),
-                ("text".to_string(), sentence.clone().into()),
-            ]
-            .into_iter()
-            .collect::<HashMap<_, _>>();
-
-            PointStruct::new(
-                point_id,
-                qdrant_client::qdrant::Vectors::from(embedding),
-                payload,
-            )
-        })
-        .collect();
-
-    println!("Generated {} embeddings", points.len());
-
-    Ok(ProcessingResult {
-        total_points: start_id + points.len() as u64,
-        points,
-    })
-}
-
-// バッチupsert（エラーハンドリング付き）
-async fn upsert_batch(
-    client: &qdrant_client::Qdrant,
-    collection_name: &str,
-    batch_points: &mut Vec<PointStruct>,
-) -> Result<()> {
-    if batch_points.is_empty() {
-        return Ok(());
+// 再開可能なチェックポイント（参照先のバージョンが残っている場合のみ）
+async fn resumable_checkpoint(client: &Qdrant, config: &Config) -> Result<Option<Checkpoint>> {
+    let Some(checkpoint) = Checkpoint::load(&config.checkpoint_path)? else {
+        return Ok(None);
+    };
+    if collections::parse_version(&config.collection, &checkpoint.collection).is_none() {
+        return Ok(None);
     }
 
-    println!("Batch upserting {} points to Qdrant...", batch_points.len());
+    let exists = client.collection_exists(&checkpoint.collection).await?;
+    Ok(exists.then_some(checkpoint))
+}
 
-    client
-        .upsert_points(UpsertPointsBuilder::new(
-            collection_name,
-            batch_points.clone(),
-        ))
+// 標準入力のテキストを公開中のコレクションへ投入
+async fn ingest_stdin(client: Qdrant, config: &Config, args: &IngestArgs) -> Result<()> {
+    let title = args.title.as_deref().context("--title is required")?;
+    let mut text = String::new();
+    tokio::io::stdin()
+        .read_to_string(&mut text)
         .await
-        .context("Failed to upsert points")?;
+        .context("Failed to read standard input as UTF-8")?;
+    let metadata = args
+        .metadata
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone().into()))
+        .collect::<HashMap<String, Value>>();
 
-    batch_points.clear();
-    println!("Batch upsert completed");
+    let models = config.vectors.select(&args.models)?;
+    let mut ingester = TextIngester::new(client, config.clone(), &models).await?;
+    if let Some(tenant) = &args.tenant {
+        ingester = ingester.with_tenant(tenant);
+    }
+    let ingested = ingester.ingest(title, &text, metadata.into()).await?;
+
+    if args.json {
+        println!("{}", serde_json::to_string(&ingested)?);
+    } else {
+        println!(
+            "Ingested {} points from {} lines ({} duplicates) into {} as {}",
+            ingested.points,
+            ingested.lines,
+            ingested.duplicates,
+            ingested.collection,
+            ingested.source
+        );
+    }
     Ok(())
 }
 
-// ファイルから非空行を読み取るイテレータ
-fn read_non_empty_lines(
-    file_path: &std::path::Path,
-    buffer_size: usize,
-) -> Result<impl Iterator<Item = Result<String>>> {
-    let file = File::open(file_path)
-        .with_context(|| format!("Failed to open file: {}", file_path.display()))?;
-
-    let reader = BufReader::with_capacity(buffer_size, file);
-
-    Ok(reader
-        .lines()
-        .map(|line| line.context("Failed to read line"))
-        .filter_map(|line| match line {
-            Ok(content) if !content.trim().is_empty() => Some(Ok(content)),
-            Ok(_) => None, // 空行をスキップ
-            Err(e) => Some(Err(e)),
-        }))
+// ローダー用に全モデルを読み込んだ投入器（テナント省略時は default_tenant）
+async fn loader_ingester(
+    client: Qdrant,
+    config: &Config,
+    tenant: Option<String>,
+) -> Result<TextIngester> {
+    let ingester = TextIngester::new(client, config.clone(), &[]).await?;
+    Ok(match tenant {
+        Some(tenant) => ingester.with_tenant(tenant),
+        None => ingester,
+    })
 }
 
-// ファイル処理の中核ロジック
-async fn process_file(
-    client: &qdrant_client::Qdrant,
-    collection_name: &str,
-    file_path: std::path::PathBuf,
-    config: &ProcessingConfig,
-    mut current_id: u64,
-) -> Result<u64> {
-    let title = file_path
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("unknown")
-        .to_string();
-
-    println!("Processing file: {}", title);
-
-    let lines = read_non_empty_lines(&file_path, config.buffer_size)?;
-    let mut chunk_buffer = Vec::with_capacity(config.chunk_size);
-    let mut batch_points = Vec::new();
-
-    for line_result in lines {
-        let line = line_result?;
-        chunk_buffer.push(line);
-
-        // チャンク処理
-        if chunk_buffer.len() >= config.chunk_size {
-            let result = process_chunk(&chunk_buffer, current_id, &title).await?;
-            current_id = result.total_points;
-            batch_points.extend(result.points);
-
-            // バッチ処理
-            if batch_points.len() >= config.batch_size * config.chunk_size {
-                upsert_batch(client, collection_name, &mut batch_points).await?;
+// レポートを書き出す（書き出し先の指定がなければ何もしない）
+fn save_report(report: &mut IngestReport, path: Option<&Path>, mode: OutputMode) {
+    let Some(path) = path else {
+        return;
+    };
+    match report.save(path) {
+        Ok(()) => {
+            if matches!(mode, OutputMode::Interactive | OutputMode::Plain) {
+                println!("Wrote report to {}", path.display());
             }
-
-            chunk_buffer.clear();
         }
+        // 実行結果のエラーを優先するため、書き出しの失敗は報告のみ
+        Err(e) => eprintln!("Failed to write report: {:#}", e),
     }
+}
 
-    // 残りのチャンクを処理
-    if !chunk_buffer.is_empty() {
-        let result = process_chunk(&chunk_buffer, current_id, &title).await?;
-        current_id = result.total_points;
-        batch_points.extend(result.points);
+// 新しいバージョンへ投入し、検証後にエイリアスを切り替える
+async fn ingest(client: Qdrant, config: &Config, args: &IngestArgs) -> Result<()> {
+    if args.input.is_some() {
+        return ingest_stdin(client, config, args).await;
     }
 
-    // 残りのバッチを処理
-    if !batch_points.is_empty() {
-        upsert_batch(client, collection_name, &mut batch_points).await?;
+    // ドライランでは計画を表示して終了（チェックポイントやコレクションには触れない）
+    if args.dry_run {
+        let files = collect_files(config)?;
+        let models = config.vectors.select(&args.models)?;
+        let mode = OutputMode::detect(args.quiet, args.json);
+        plan::build(&client, files, config, &models)
+            .await?
+            .print(mode);
+        return Ok(());
     }
 
-    println!("Completed processing file: {}", title);
-    Ok(current_id)
+    // どの段階で失敗してもレポートを書き出す（しきい値超過は結果を上書きしない）
+    let report_path = args.report.as_deref().or(config.report.path.as_deref());
+    let mut report = IngestReport::new(&config.collection);
+    let result = ingest_files(client, config, args, &mut report).await;
+    match &result {
+        Err(e) if report.status != RunStatus::ThresholdExceeded => report.failed(e),
+        _ => {}
+    }
+    save_report(
+        &mut report,
+        report_path,
+        OutputMode::detect(args.quiet, args.json),
+    );
+    result
 }
 
-// コレクション初期化
-async fn initialize_collection(
-    client: &qdrant_client::Qdrant,
-    collection_name: &str,
+// ファイルを新しいバージョンへ投入して公開する（結果は report に記録する）
+async fn ingest_files(
+    client: Qdrant,
+    config: &Config,
+    args: &IngestArgs,
+    report: &mut IngestReport,
 ) -> Result<()> {
-    let _ = client.delete_collection(collection_name).await; // エラー無視（存在しない場合）
+    let alias = config.collection.as_str();
+    let files = collect_files(config)?;
+    let models = config.vectors.select(&args.models)?;
+    report.models = models.iter().copied().map(ModelReport::from).collect();
+
+    // 再開時は前回のチェックポイントを読み込み、完了済みファイルを除外
+    let checkpoint = if args.resume {
+        resumable_checkpoint(&client, config).await?
+    } else {
+        Checkpoint::remove(&config.checkpoint_path)?;
+        None
+    };
+    // 新規インジェストでは前回のデッドレターは古いバージョン向けのため破棄（再開時は追記）
+    if checkpoint.is_none() {
+        DeadLetter::save_all(&config.failures.dead_letter_path, &[])?;
+    }
+    let resuming = checkpoint.is_some();
 
-    client
-        .create_collection(
-            CreateCollectionBuilder::new(collection_name)
-                .vectors_config(VectorParamsBuilder::new(512, Distance::Cosine)),
-        )
-        .await
-        .context("Failed to create collection")?;
+    let progress_mode = OutputMode::detect(args.quiet, args.json);
+    let (checkpoint, files, completed) = match checkpoint {
+        Some(checkpoint) => {
+            let total = files.len();
+            let files: Vec<_> = files
+                .into_iter()
+                .filter(|(path, _)| !checkpoint.is_completed(&pipeline::source_name(path)))
+                .collect();
+            let completed = total - files.len();
+            (checkpoint, files, completed)
+        }
+        // 公開中のコレクションには触れず、新しいバージョンを作成
+        None => {
+            let collection_name = collections::create_next_version(
+                &client,
+                alias,
+                &config.schema,
+                &config.vectors,
+                &config.tuning,
+            )
+            .await?;
+            (Checkpoint::new(&collection_name), files, 0)
+        }
+    };
+    let (file_paths, tenants): (Vec<_>, Vec<_>) = files.into_iter().unzip();
+    let collection_name = checkpoint.collection.clone();
+    report.set_collection(&collection_name);
+    report.resumed = resuming;
+    report.files_already_completed = completed as u64;
+
+    let progress = Progress::new(progress_mode, &file_paths);
+    if resuming {
+        progress.log(&format!(
+            "Resuming ingestion into {}: {} files already completed",
+            collection_name, completed
+        ));
+    } else {
+        if args.resume {
+            progress.log("No checkpoint to resume from; starting a fresh ingestion");
+        }
+        progress.log(&format!("Building collection {}", collection_name));
+    }
+    progress.log("Loading data from files...");
+
+    let embedders = start_embedders(config, models).await?;
+    progress.log(&format!(
+        "Started {} embedding model instances for {}",
+        embedders.instances(),
+        embedders
+            .models()
+            .iter()
+            .map(|model| model.name())
+            .collect::<Vec<_>>()
+            .join(", ")
+    ));
+
+    let sources = file_paths
+        .iter()
+        .map(|path| pipeline::source_name(path))
+        .collect();
+    let pipeline = Arc::new(Pipeline {
+        client: client.clone(),
+        collection_name: collection_name.clone(),
+        files: file_paths,
+        tenants,
+        config: config.processing.clone(),
+        embedders,
+        schema: config.schema.clone(),
+        encoding: config.encoding.clone(),
+        normalize: config.normalize.clone(),
+        context: config.context.clone(),
+        parents: config.parents.clone(),
+        dedup: config.dedup.clone(),
+        failures: config.failures.clone(),
+        dead_letters: DeadLetterWriter::new(&config.failures.dead_letter_path),
+        checkpoint: CheckpointTracker::new(&config.checkpoint_path, sources, checkpoint),
+        progress: Arc::clone(&progress),
+        stats: PipelineStats::default(),
+    });
+
+    // パイプラインで並行処理（失敗時もサマリーを出力）
+    let renderer = progress.spawn_renderer();
+    let result = pipeline::run(&pipeline).await;
+    renderer.stop().await;
+
+    let summary = progress.summary(&pipeline.stats);
+    summary.print(progress.mode());
+
+    report.violations = config.report.violations(&summary);
+    report.totals = summary;
+    report.files = progress.file_reports();
+    result?;
+
+    if report.totals.dead_lettered > 0 {
+        progress.log(&format!(
+            "{} chunks were written to {}; retry them with `replay`",
+            report.totals.dead_lettered,
+            pipeline.dead_letters.path().display()
+        ));
+    }
 
-    Ok(())
+    // しきい値を超えた場合は公開せず、チェックポイントを残す（replay 後に --resume で公開できる）
+    if !report.violations.is_empty() {
+        report.status = RunStatus::ThresholdExceeded;
+        anyhow::bail!(
+            "Ingestion exceeded error thresholds ({}); alias {} was not switched",
+            report.violations.join(", "),
+            alias
+        );
+    }
+
+    // ローダーが公開中のバージョンへ投入したポイントを引き継いでから公開する
+    let carried = text::carry_over_loader_points(&client, config, &collection_name).await?;
+    if carried > 0 {
+        progress.log(&format!(
+            "Carried {} points from loaders into {}",
+            carried, collection_name
+        ));
+    }
+    report.carried_points = carried;
+
+    // 検証に通った場合のみエイリアスを切り替える（失敗時はチェックポイントを残す）
+    let published = collections::publish(
+        &client,
+        alias,
+        &collection_name,
+        pipeline.stats.upsert.items() + carried,
+        resuming,
+        config.keep_versions,
+        &config.processing,
+    )
+    .await?;
+    progress.log(&format!(
+        "Alias {} now points to {} ({} points, previously {})",
+        alias,
+        collection_name,
+        published.count,
+        published.previous.as_deref().unwrap_or("none")
+    ));
+    if published.retired_legacy {
+        progress.log(&format!(
+            "Moved legacy collection {} to {}; searches failed until the alias was created",
+            alias,
+            published.previous.as_deref().unwrap_or("none")
+        ));
+    }
+    for deleted in &published.deleted {
+        progress.log(&format!("Deleted old collection {}", deleted));
+    }
+    report.published = true;
+    report.previous_collection = published.previous;
+    report.retired_legacy = published.retired_legacy;
+    report.deleted_collections = published.deleted;
+
+    // 正常終了したらチェックポイントは不要
+    Checkpoint::remove(&config.checkpoint_path)
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
     let client = get_qdrant_client();
-    let collection_name = "knowledge";
-    let config = ProcessingConfig::default();
-
-    // コレクション初期化
-    initialize_collection(&client, collection_name).await?;
-    println!("Loading data from files...");
+    let config = Config::load(&cli.config)?;
+
+    match cli.command.unwrap_or(Command::Ingest(cli.ingest)) {
+        Command::Ingest(args) => ingest(client, &config, &args).await,
+        Command::Rollback { to } => {
+            let collection_name =
+                collections::rollback(&client, &config.collection, to, &config.processing).await?;
+            println!(
+                "Alias {} now points to {}",
+                config.collection, collection_name
+            );
+            Ok(())
+        }
+        Command::Export { path, format } => {
+            let exported = transfer::export(&client, &config, &path, format).await?;
+            println!("Exported {} points to {}", exported, path.display());
+            Ok(())
+        }
+        Command::Import {
+            path,
+            format,
+            batch_size,
+        } => {
+            let imported = transfer::import(&client, &config, &path, format, batch_size).await?;
+            let published = &imported.published;
+            println!(
+                "Imported {} points from {}",
+                imported.points,
+                path.display()
+            );
+            println!(
+                "Alias {} now points to {} (previously {})",
+                config.collection,
+                imported.collection,
+                published.previous.as_deref().unwrap_or("none")
+            );
+            if published.retired_legacy {
+                println!(
+                    "Moved legacy collection {} to {}; searches failed until the alias was created",
+                    config.collection,
+                    published.previous.as_deref().unwrap_or("none")
+                );
+            }
+            for deleted in &published.deleted {
+                println!("Deleted old collection {}", deleted);
+            }
+            Ok(())
+        }
+        Command::UpdateCollection { collection } => {
+            let collection_name = match collection {
+                Some(collection) => collection,
+                None => collections::resolve(&client, &config.collection)
+                    .await?
+                    .with_context(|| format!("Collection {} does not exist", config.collection))?,
+            };
+            tuning::update(&client, &collection_name, &config.tuning, &config.vectors).await?;
+            println!("Updated collection {}", collection_name);
+            Ok(())
+        }
+        Command::Replay { dead_letter } => {
+            let path = dead_letter.unwrap_or_else(|| config.failures.dead_letter_path.clone());
+            if DeadLetter::load_all(&path)?.is_empty() {
+                println!("No dead letters in {}", path.display());
+                return Ok(());
+            }
 
-    // ファイルパターンからファイルリストを取得
-    let file_paths: Result<Vec<_>> = ["data/*.txt", "data/*.md"]
-        .iter()
-        .flat_map(|pattern| {
-            glob(pattern)
-                .context("Failed to read glob pattern")
-                .into_iter()
-                .flatten()
-        })
-        .collect::<std::result::Result<Vec<_>, _>>()
-        .context("Failed to collect file paths");
+            let embedders = start_embedders(&config, config.vectors.models()).await?;
+            let replayed = failures::replay(&client, &config, &embedders, &path).await?;
+            println!(
+                "Replayed {} of {} chunks ({} points); {} remain in {}",
+                replayed.chunks - replayed.remaining,
+                replayed.chunks,
+                replayed.points,
+                replayed.remaining,
+                path.display()
+            );
+            Ok(())
+        }
+        Command::Git {
+            repo,
+            files_at,
+            full,
+            tenant,
+        } => {
+            let ingester = loader_ingester(client, &config, tenant).await?;
+            let ingested = git::ingest(&ingester, &config.git, &repo, &files_at, full).await?;
+            println!(
+                "Ingested {} commits{} and {} files ({} points) from {}",
+                ingested.commits,
+                if ingested.incremental {
+                    " (incremental)"
+                } else {
+                    ""
+                },
+                ingested.files,
+                ingested.points,
+                repo.display()
+            );
+            if ingested.skipped_files > 0 {
+                println!("Skipped {} non-UTF-8 files", ingested.skipped_files);
+            }
+            Ok(())
+        }
+        Command::Mail { path, tenant } => {
+            let ingester = loader_ingester(client, &config, tenant).await?;
+            let ingested = mail::ingest(&ingester, &path).await?;
+            println!(
+                "Ingested {} messages ({} points) from {}; skipped {}",
+                ingested.messages,
+                ingested.points,
+                path.display(),
+                ingested.skipped
+            );
+            Ok(())
+        }
+        Command::Chat { path, tenant } => {
+            let ingester = loader_ingester(client, &config, tenant).await?;
+            let ingested = chat::ingest(&ingester, &config.chat, &path).await?;
+            println!(
+                "Ingested {} messages from {} files as {} conversations in {} channels ({} points)",
+                ingested.messages,
+                ingested.files,
+                ingested.windows,
+                ingested.channels,
+                ingested.points
+            );
+            Ok(())
+        }
+        Command::Migrate {
+            collection,
+            batch_size,
+            dry_run,
+        } => {
+            let migrated =
+                migrate::migrate(&client, &config, collection, batch_size, dry_run).await?;
+            if migrated.points == 0 {
+                println!(
+                    "All points in {} are at schema version {}",
+                    migrated.collection,
+                    migrate::SCHEMA_VERSION
+                );
+                return Ok(());
+            }
 
-    // 各ファイルを順次処理
-    let mut current_id = 0u64;
-    for file_path in file_paths? {
-        current_id = process_file(&client, collection_name, file_path, &config, current_id).await?;
+            println!(
+                "{} {} points in {} to schema version {}",
+                if dry_run { "Would migrate" } else { "Migrated" },
+                migrated.points,
+                migrated.collection,
+                migrate::SCHEMA_VERSION
+            );
+            for (version, points) in &migrated.from_versions {
+                println!("  from v{}: {} points", version, points);
+            }
+            for migration in &migrated.applied {
+                println!("  {}", migration);
+            }
+            Ok(())
+        }
     }
-
-    println!("Processing completed. Total points: {}", current_id);
-    Ok(())
 }
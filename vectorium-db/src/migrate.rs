@@ -0,0 +1,331 @@
+use anyhow::{Context, Result};
+use glob::glob;
+use qdrant_client::Qdrant;
+use qdrant_client::qdrant::points_update_operation::{Operation, OverwritePayload};
+use qdrant_client::qdrant::value::Kind;
+use qdrant_client::qdrant::{
+    Condition, Filter, PointId, PointsUpdateOperation, Range, ScrollPointsBuilder,
+    UpdateBatchPointsBuilder, Value,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use crate::collections;
+use crate::config::Config;
+use crate::pipeline::{content_hash, source_name};
+
+// ペイロードの移行（version はこの移行を適用した後のスキーマバージョン）
+struct Migration {
+    version: i64,
+    description: &'static str,
+    apply: fn(&mut HashMap<String, Value>, &MigrationContext),
+}
+
+// 元のファイルを特定できなかった初期のポイントの source に付ける接頭辞
+const MIGRATED_SOURCE_PREFIX: &str = "migrated:";
+
+// 移行で作った仮の source か（plan では削除対象として扱わない）
+pub fn is_migrated_source(source: &str) -> bool {
+    source.starts_with(MIGRATED_SOURCE_PREFIX)
+}
+
+// 移行に使う設定と、初期のポイントの title（ファイル名）から source を復元するための対応
+pub struct MigrationContext<'a> {
+    config: &'a Config,
+    // ファイル名 -> sources に一致するファイルの source
+    sources: HashMap<String, Vec<String>>,
+}
+
+impl<'a> MigrationContext<'a> {
+    // テナント導入前のポイントのため、default_tenant の sources だけを対象にする
+    pub fn new(config: &'a Config) -> Result<Self> {
+        let mut paths = Vec::new();
+        for pattern in &config.sources {
+            for path in glob(pattern).context("Failed to read glob pattern")? {
+                paths.push(path.context("Failed to collect file paths")?);
+            }
+        }
+        Ok(Self::from_paths(config, paths))
+    }
+
+    pub fn from_paths(config: &'a Config, paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        let mut sources: HashMap<String, Vec<String>> = HashMap::new();
+        for path in paths {
+            if let Some(name) = path.file_name() {
+                sources
+                    .entry(name.to_string_lossy().to_string())
+                    .or_default()
+                    .push(source_name(&path));
+            }
+        }
+        Self { config, sources }
+    }
+
+    // ファイル名が1つのファイルにだけ一致する場合、その source
+    fn source(&self, title: &str) -> Option<&str> {
+        match self.sources.get(title).map(Vec::as_slice) {
+            Some([source]) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// 古い順に並べる（ペイロードにフィールドを追加したら、ここに移行を追加する）
+const MIGRATIONS: [Migration; 2] = [
+    Migration {
+        version: 1,
+        description: "add source and content_hash",
+        apply: add_source_and_hash,
+    },
+    Migration {
+        version: 2,
+        description: "add tenant_id",
+        apply: add_tenant,
+    },
+];
+
+// 新しく投入するポイントに付けるスキーマバージョン（最後の移行のバージョン）
+pub const SCHEMA_VERSION: i64 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+fn string_field(payload: &HashMap<String, Value>, key: &str) -> Option<String> {
+    match payload.get(key).and_then(|value| value.kind.as_ref()) {
+        Some(Kind::StringValue(value)) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+// 初期のポイントは title（ファイル名）と text のみ
+// source は現在の sources から復元し、特定できなければ移行した印を付ける
+fn add_source_and_hash(payload: &mut HashMap<String, Value>, context: &MigrationContext) {
+    if let Some(title) = string_field(payload, "title").filter(|_| !payload.contains_key("source"))
+    {
+        let source = match context.source(&title) {
+            Some(source) => source.to_string(),
+            None => format!("{}{}", MIGRATED_SOURCE_PREFIX, title),
+        };
+        payload.insert("source".to_string(), source.into());
+    }
+    if let Some(text) =
+        string_field(payload, "text").filter(|_| !payload.contains_key("content_hash"))
+    {
+        payload.insert("content_hash".to_string(), content_hash(&text).into());
+    }
+}
+
+// テナント導入前のポイントは default_tenant に属する
+fn add_tenant(payload: &mut HashMap<String, Value>, context: &MigrationContext) {
+    payload
+        .entry("tenant_id".to_string())
+        .or_insert_with(|| context.config.default_tenant.clone().into());
+}
+
+// 移行結果
+#[derive(Debug, Default)]
+pub struct Migrated {
+    pub collection: String,
+    // 移行が必要だったポイント
+    pub points: u64,
+    // 移行前のスキーマバージョン -> ポイント数
+    pub from_versions: BTreeMap<i64, u64>,
+    // 適用した移行（"v2: add tenant_id" の形式）
+    pub applied: Vec<String>,
+}
+
+// 1ポイント分のペイロードを現在のスキーマバージョンまで移行する（移行前のバージョンを返す）
+pub fn migrate_payload(payload: &mut HashMap<String, Value>, context: &MigrationContext) -> i64 {
+    let version = match payload.get("schema_version").and_then(|v| v.kind.as_ref()) {
+        Some(Kind::IntegerValue(version)) => *version,
+        _ => 0,
+    };
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+        (migration.apply)(payload, context);
+    }
+    payload.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+    version
+}
+
+// 古いスキーマバージョンのポイントを順に読み、ペイロードだけを書き換える（ベクトルは埋め込み直さない）
+pub async fn migrate(
+    client: &Qdrant,
+    config: &Config,
+    collection: Option<String>,
+    batch_size: u32,
+    dry_run: bool,
+) -> Result<Migrated> {
+    let collection_name = match collection {
+        Some(collection) => collection,
+        None => collections::resolve(client, &config.collection)
+            .await?
+            .with_context(|| format!("Collection {} does not exist", config.collection))?,
+    };
+    let context = MigrationContext::new(config)?;
+    // schema_version がない、または現在より古いポイント
+    let outdated = Filter::should([
+        Condition::is_empty("schema_version"),
+        Condition::range(
+            "schema_version",
+            Range {
+                lt: Some(SCHEMA_VERSION as f64),
+                ..Default::default()
+            },
+        ),
+    ]);
+
+    let mut result = Migrated {
+        collection: collection_name.clone(),
+        ..Migrated::default()
+    };
+    let mut offset: Option<PointId> = None;
+    loop {
+        let mut request = ScrollPointsBuilder::new(&collection_name)
+            .filter(outdated.clone())
+            .limit(batch_size.max(1))
+            .with_payload(true)
+            .with_vectors(false);
+        if let Some(offset) = offset.take() {
+            request = request.offset(offset);
+        }
+
+        let response = client
+            .scroll(request)
+            .await
+            .context("Failed to scroll points")?;
+
+        let mut operations = Vec::with_capacity(response.result.len());
+        for point in response.result {
+            let mut payload = point.payload;
+            let version = migrate_payload(&mut payload, &context);
+            config
+                .schema
+                .validate(&payload)
+                .with_context(|| format!("Migrated payload is invalid for {:?}", point.id))?;
+
+            *result.from_versions.entry(version).or_default() += 1;
+            result.points += 1;
+            operations.push(PointsUpdateOperation {
+                operation: Some(Operation::OverwritePayload(OverwritePayload {
+                    payload,
+                    points_selector: point.id.map(|id| vec![id].into()),
+                    ..Default::default()
+                })),
+            });
+        }
+
+        if !dry_run && !operations.is_empty() {
+            client
+                .update_points_batch(
+                    UpdateBatchPointsBuilder::new(&collection_name, operations).wait(true),
+                )
+                .await
+                .context("Failed to overwrite payloads")?;
+        }
+
+        match response.next_page_offset {
+            Some(next) => offset = Some(next),
+            None => break,
+        }
+    }
+
+    let oldest = result.from_versions.keys().next().copied();
+    result.applied = MIGRATIONS
+        .iter()
+        .filter(|migration| oldest.is_some_and(|oldest| migration.version > oldest))
+        .map(|migration| format!("v{}: {}", migration.version, migration.description))
+        .collect();
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(fields: &[(&str, Value)]) -> HashMap<String, Value> {
+        fields
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect()
+    }
+
+    fn context(config: &Config) -> MigrationContext<'_> {
+        MigrationContext::from_paths(
+            config,
+            [
+                PathBuf::from("data/notes.txt"),
+                PathBuf::from("data/a/readme.md"),
+                PathBuf::from("data/b/readme.md"),
+            ],
+        )
+    }
+
+    #[test]
+    fn v0_points_get_source_from_config_sources() {
+        let config = Config::default();
+        let context = context(&config);
+
+        let mut found = payload(&[("title", "notes.txt".into()), ("text", "hello".into())]);
+        assert_eq!(migrate_payload(&mut found, &context), 0);
+        assert_eq!(
+            string_field(&found, "source").as_deref(),
+            Some("data/notes.txt")
+        );
+        assert_eq!(
+            string_field(&found, "content_hash"),
+            Some(content_hash("hello"))
+        );
+        assert_eq!(
+            string_field(&found, "tenant_id").as_deref(),
+            Some("default")
+        );
+        assert_eq!(found.get("schema_version"), Some(&SCHEMA_VERSION.into()));
+
+        // 同名のファイルが複数ある、または見つからない場合は移行した印を付ける
+        for title in ["readme.md", "deleted.txt"] {
+            let mut missing = payload(&[("title", title.into()), ("text", "hello".into())]);
+            migrate_payload(&mut missing, &context);
+            let source = string_field(&missing, "source").unwrap();
+            assert!(is_migrated_source(&source));
+            assert!(source.ends_with(title));
+        }
+    }
+
+    #[test]
+    fn v1_points_only_get_tenant() {
+        let config = Config::default();
+        let context = context(&config);
+        let mut v1 = payload(&[
+            ("title", "notes.txt".into()),
+            ("text", "hello".into()),
+            ("source", "/srv/data/notes.txt".into()),
+            ("content_hash", "stored".into()),
+            ("schema_version", 1i64.into()),
+        ]);
+
+        assert_eq!(migrate_payload(&mut v1, &context), 1);
+        assert_eq!(
+            string_field(&v1, "source").as_deref(),
+            Some("/srv/data/notes.txt")
+        );
+        assert_eq!(string_field(&v1, "content_hash").as_deref(), Some("stored"));
+        assert_eq!(string_field(&v1, "tenant_id").as_deref(), Some("default"));
+        assert_eq!(v1.get("schema_version"), Some(&SCHEMA_VERSION.into()));
+    }
+
+    #[test]
+    fn current_points_are_unchanged() {
+        let config = Config::default();
+        let context = context(&config);
+        let current = payload(&[
+            ("title", "notes.txt".into()),
+            ("text", "hello".into()),
+            ("source", "data/notes.txt".into()),
+            ("content_hash", content_hash("hello").into()),
+            ("tenant_id", "acme".into()),
+            ("schema_version", SCHEMA_VERSION.into()),
+        ]);
+
+        let mut migrated = current.clone();
+        assert_eq!(migrate_payload(&mut migrated, &context), SCHEMA_VERSION);
+        assert_eq!(migrated, current);
+    }
+}
@@ -0,0 +1,66 @@
+use serde::Deserialize;
+use unicode_normalization::UnicodeNormalization;
+
+// 埋め込み前のテキスト正規化の設定
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NormalizeConfig {
+    // NFKC正規化（全角英数字・半角カナなどの表記揺れを統一）
+    pub nfkc: bool,
+    // 連続する空白を1つの半角スペースにまとめ、前後の空白を除去
+    pub collapse_whitespace: bool,
+    // ゼロ幅文字（ZWSP, ZWJ, BOMなど）を除去
+    pub remove_zero_width: bool,
+    // 制御文字を除去
+    pub remove_control: bool,
+    pub lowercase: bool,
+}
+
+impl Default for NormalizeConfig {
+    fn default() -> Self {
+        Self {
+            nfkc: true,
+            collapse_whitespace: true,
+            remove_zero_width: true,
+            remove_control: true,
+            lowercase: false,
+        }
+    }
+}
+
+impl NormalizeConfig {
+    // 埋め込み・重複判定に使うテキストを作成（表示用の原文は変更しない）
+    pub fn apply(&self, text: &str) -> String {
+        let text: String = if self.nfkc {
+            text.nfkc().collect()
+        } else {
+            text.to_string()
+        };
+
+        let text: String = text
+            .chars()
+            .filter(|&c| !(self.remove_zero_width && is_zero_width(c)))
+            // 空白扱いの制御文字（タブなど）は空白の正規化に任せる
+            .filter(|&c| !(self.remove_control && c.is_control() && !c.is_whitespace()))
+            .collect();
+
+        let text = if self.collapse_whitespace {
+            text.split_whitespace().collect::<Vec<_>>().join(" ")
+        } else {
+            text
+        };
+
+        if self.lowercase {
+            text.to_lowercase()
+        } else {
+            text
+        }
+    }
+}
+
+fn is_zero_width(c: char) -> bool {
+    matches!(
+        c,
+        '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}' | '\u{00AD}'
+    )
+}
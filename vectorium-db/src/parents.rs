@@ -0,0 +1,326 @@
+use anyhow::Result;
+use qdrant_client::qdrant::Value;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, OnceLock};
+
+use crate::checkpoint::OpenSection;
+use crate::context::parse_heading;
+use crate::pipeline::Line;
+
+// 親子チャンク（行ごとに検索し、結果には行を含むセクション全体を返せるようにする）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ParentConfig {
+    pub enabled: bool,
+    // 見出しがない場合も、この行数ごとに別のセクションにする
+    pub max_lines: usize,
+}
+
+impl Default for ParentConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_lines: 40,
+        }
+    }
+}
+
+// 親セクション（本文はセクション内の最初に投入される行のペイロードに1度だけ保存する）
+#[derive(Debug)]
+pub struct Section {
+    pub text: String,
+    // セクションの先頭の行番号
+    pub start: u64,
+    // 本文を持つポイントのID（チャンク化ステージで最初の行に決まる）
+    parent_id: OnceLock<String>,
+}
+
+impl Section {
+    fn new(text: String, start: u64) -> Self {
+        Self {
+            text,
+            start,
+            parent_id: OnceLock::new(),
+        }
+    }
+
+    // デッドレターから復元（本文は本文を持つポイントの分のみ、再開には使わないため先頭の行番号は持たない）
+    pub fn restore(parent_id: String, text: Option<String>) -> Self {
+        Self {
+            text: text.unwrap_or_default(),
+            start: 0,
+            parent_id: OnceLock::from(parent_id),
+        }
+    }
+
+    // チェックポイントに残す親セクション（親IDが決まっている場合のみ）
+    pub fn open(&self) -> Option<OpenSection> {
+        Some(OpenSection {
+            start: self.start,
+            parent_id: self.parent_id()?.to_string(),
+        })
+    }
+
+    // 行のポイントに加える親セクションのペイロード（本文は親IDのポイントにのみ保存する）
+    pub fn payload(&self, point_id: &str) -> Vec<(String, Value)> {
+        let Some(parent_id) = self.parent_id() else {
+            return Vec::new();
+        };
+
+        let mut payload = vec![("parent_id".to_string(), parent_id.to_string().into())];
+        if parent_id == point_id {
+            payload.push(("parent_text".to_string(), self.text.clone().into()));
+        }
+        payload
+    }
+
+    pub fn parent_id(&self) -> Option<&str> {
+        self.parent_id.get().map(String::as_str)
+    }
+
+    // 最初に呼ばれた行のポイントIDを親IDにする
+    pub fn assign(&self, point_id: impl FnOnce() -> String) -> &str {
+        self.parent_id.get_or_init(point_id)
+    }
+}
+
+// 行を見出しまたは行数の上限で区切ってセクションにまとめる（無効な場合はそのまま返す）
+pub struct Sections<I> {
+    lines: I,
+    config: ParentConfig,
+    pending: VecDeque<Result<Line>>,
+    // 次のセクションの先頭になる見出しの行
+    carry: Option<Line>,
+    // 次のセクションの先頭の行番号
+    next_start: u64,
+    // 再開位置をまたぐセクション（再開前に決まった親IDを引き継ぐ）
+    resume: Option<OpenSection>,
+}
+
+impl<I: Iterator<Item = Result<Line>>> Sections<I> {
+    pub fn new(lines: I, config: &ParentConfig, resume: Option<OpenSection>) -> Self {
+        Self {
+            lines,
+            config: config.clone(),
+            pending: VecDeque::new(),
+            carry: None,
+            next_start: 0,
+            resume,
+        }
+    }
+
+    // 次のセクションを読み込んで pending に積む
+    fn fill(&mut self) {
+        let max_lines = self.config.max_lines.max(1);
+        let mut buffer: Vec<Line> = self.carry.take().into_iter().collect();
+        let mut error = None;
+
+        while buffer.len() < max_lines {
+            match self.lines.next() {
+                Some(Ok(line)) => {
+                    if !buffer.is_empty() && parse_heading(line.text.trim()).is_some() {
+                        self.carry = Some(line);
+                        break;
+                    }
+                    buffer.push(line);
+                }
+                Some(Err(e)) => {
+                    error = Some(e);
+                    break;
+                }
+                None => break,
+            }
+        }
+
+        if !buffer.is_empty() {
+            let text = buffer
+                .iter()
+                .map(|line| line.text.as_str())
+                .collect::<Vec<_>>()
+                .join("\n");
+            let section = Section::new(text, self.next_start);
+            self.next_start += buffer.len() as u64;
+            if let Some(open) = self.resume.take_if(|open| open.start == section.start) {
+                section.assign(|| open.parent_id);
+            }
+            let section = Arc::new(section);
+            self.pending.extend(buffer.into_iter().map(|mut line| {
+                line.section = Some(Arc::clone(&section));
+                Ok(line)
+            }));
+        }
+        if let Some(e) = error {
+            self.pending.push_back(Err(e));
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<Line>>> Iterator for Sections<I> {
+    type Item = Result<Line>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.config.enabled {
+            return self.lines.next();
+        }
+        if self.pending.is_empty() {
+            self.fill();
+        }
+        self.pending.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::Neighbors;
+
+    fn lines(texts: &[&str]) -> impl Iterator<Item = Result<Line>> {
+        texts
+            .iter()
+            .map(|text| {
+                Ok(Line {
+                    text: text.to_string(),
+                    normalized: text.to_string(),
+                    context: None,
+                    section: None,
+                    neighbors: Neighbors::default(),
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    fn section(line: &Line) -> &Section {
+        line.section.as_deref().expect("line has no section")
+    }
+
+    fn enabled(max_lines: usize) -> ParentConfig {
+        ParentConfig {
+            enabled: true,
+            max_lines,
+        }
+    }
+
+    // 行ごとの (セクションの先頭の行番号, セクションの本文)
+    fn sections(texts: &[&str], config: &ParentConfig) -> Vec<(u64, String)> {
+        Sections::new(lines(texts), config, None)
+            .map(|line| {
+                let line = line.unwrap();
+                let section = section(&line);
+                (section.start, section.text.clone())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn headings_start_a_new_section() {
+        let texts = ["intro", "# A", "a1", "## B", "b1", "not # a heading"];
+        let expected = [
+            (0, "intro"),
+            (1, "# A\na1"),
+            (1, "# A\na1"),
+            (3, "## B\nb1\nnot # a heading"),
+            (3, "## B\nb1\nnot # a heading"),
+            (3, "## B\nb1\nnot # a heading"),
+        ];
+        assert_eq!(
+            sections(&texts, &enabled(40)),
+            expected.map(|(start, text)| (start, text.to_string()))
+        );
+    }
+
+    #[test]
+    fn max_lines_splits_a_long_section() {
+        let texts = ["# A", "a1", "a2", "a3", "# B"];
+        let expected = [
+            (0, "# A\na1"),
+            (0, "# A\na1"),
+            (2, "a2\na3"),
+            (2, "a2\na3"),
+            (4, "# B"),
+        ];
+        assert_eq!(
+            sections(&texts, &enabled(2)),
+            expected.map(|(start, text)| (start, text.to_string()))
+        );
+    }
+
+    #[test]
+    fn only_the_first_assigned_point_holds_the_parent_text() {
+        let lines: Vec<Line> = Sections::new(lines(&["# A", "a1", "a2"]), &enabled(40), None)
+            .collect::<Result<_>>()
+            .unwrap();
+        let parent = section(&lines[0]);
+        assert!(parent.payload("p0").is_empty());
+
+        // チャンク化ステージと同じく、各行のポイントIDで順に親IDを決める
+        for (i, line) in lines.iter().enumerate() {
+            section(line).assign(|| format!("p{}", i));
+        }
+        assert_eq!(parent.parent_id(), Some("p0"));
+
+        let parent_id = Value::from("p0");
+        let parent_text = Value::from("# A\na1\na2");
+        assert_eq!(
+            parent.payload("p0"),
+            [
+                ("parent_id".to_string(), parent_id.clone()),
+                ("parent_text".to_string(), parent_text),
+            ]
+        );
+        for point_id in ["p1", "p2"] {
+            assert_eq!(
+                parent.payload(point_id),
+                [("parent_id".to_string(), parent_id.clone())]
+            );
+        }
+    }
+
+    #[test]
+    fn disabled_sections_pass_lines_through() {
+        let config = ParentConfig {
+            enabled: false,
+            max_lines: 1,
+        };
+        let lines: Vec<Line> = Sections::new(lines(&["# A", "a1", "# B"]), &config, None)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| line.section.is_none()));
+    }
+
+    #[test]
+    fn resuming_in_the_middle_of_a_section_keeps_its_parent_id() {
+        let texts = ["# A", "a1", "a2", "a3", "# B", "b1"];
+        let config = ParentConfig {
+            enabled: true,
+            max_lines: 40,
+        };
+
+        // 最初の実行: 先頭の行が親IDを持ち、3行をコミットした時点で中断
+        let first: Vec<Line> = Sections::new(lines(&texts), &config, None)
+            .take(3)
+            .collect::<Result<_>>()
+            .unwrap();
+        section(&first[0]).assign(|| "a".to_string());
+        let open = section(&first[2]).open();
+        assert_eq!(
+            open,
+            Some(OpenSection {
+                start: 0,
+                parent_id: "a".to_string()
+            })
+        );
+
+        // 再開: コミット済みの3行を読み飛ばすと、続きの行は同じ親IDを使う（本文は保存し直さない）
+        let resumed: Vec<Line> = Sections::new(lines(&texts), &config, open)
+            .skip(3)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(section(&resumed[0]).assign(|| "a3".to_string()), "a");
+        // 次のセクションは再開後に最初に投入する行が親IDになる
+        assert_eq!(section(&resumed[1]).start, 4);
+        assert_eq!(section(&resumed[1]).parent_id(), None);
+    }
+}
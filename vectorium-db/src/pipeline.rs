@@ -0,0 +1,1163 @@
+use anyhow::{Context, Result, anyhow};
+use prost::Message;
+use qdrant_client::Qdrant;
+use qdrant_client::qdrant::points_update_operation::{Operation, SetPayload};
+use qdrant_client::qdrant::{
+    PointId, PointStruct, PointsUpdateOperation, UpdateBatchPointsBuilder, UpsertPointsBuilder,
+    Value,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use encoding_rs::Encoding;
+use uuid::Uuid;
+
+use crate::checkpoint::{CheckpointTracker, OpenSection};
+use crate::config::ProcessingConfig;
+use crate::context::{ContextConfig, ContextTracker, embedding_text};
+use crate::dedup::{DedupConfig, Deduplicator, Location, Merged, TenantDeduplicators};
+use crate::encoding::{DecodingReader, EncodingConfig};
+use crate::failures::{DeadLetter, DeadLetterParent, DeadLetterWriter, FailureConfig};
+use crate::migrate::SCHEMA_VERSION;
+use crate::normalize::NormalizeConfig;
+use crate::parents::{ParentConfig, Section, Sections};
+use crate::progress::Progress;
+use crate::schema::PayloadSchema;
+use crate::vectors::Embedders;
+
+// 進捗へ読み込み行数を反映する間隔
+const PROGRESS_LINE_INTERVAL: u64 = 256;
+
+// ファイル読み込みステージからチャンク化ステージへのメッセージ
+enum ReaderMessage {
+    Line { file: usize, line: Line },
+    EndOfFile { file: usize },
+}
+
+// 埋め込み対象のチャンク
+struct Chunk {
+    file: usize,
+    // ファイル内のチャンク番号
+    index: u64,
+    // 各行のファイル内通し番号（重複除外により連続しない場合がある）
+    seqs: Vec<u64>,
+    lines: Vec<Line>,
+    // 除外した重複行を含め、このチャンクまでに消費した行数
+    consumed: u64,
+}
+
+// チャンク処理の結果
+pub struct ProcessingResult {
+    pub points: Vec<PointStruct>,
+}
+
+// upsertステージへ渡す埋め込み済みチャンク
+struct EmbeddedChunk {
+    file: usize,
+    index: u64,
+    // チェックポイントに記録する消費行数
+    consumed: u64,
+    // upsertに失敗した場合にデッドレターへ残す行番号と行
+    seqs: Vec<u64>,
+    lines: Vec<Line>,
+    points: Vec<PointStruct>,
+}
+
+// ステージごとの処理量と稼働時間
+pub struct StageStats {
+    name: &'static str,
+    unit: &'static str,
+    items: AtomicU64,
+    busy_nanos: AtomicU64,
+}
+
+impl StageStats {
+    fn new(name: &'static str, unit: &'static str) -> Self {
+        Self {
+            name,
+            unit,
+            items: AtomicU64::new(0),
+            busy_nanos: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, items: u64, elapsed: Duration) {
+        self.items.fetch_add(items, Ordering::Relaxed);
+        self.busy_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn unit(&self) -> &'static str {
+        self.unit
+    }
+
+    pub fn items(&self) -> u64 {
+        self.items.load(Ordering::Relaxed)
+    }
+
+    pub fn busy(&self) -> Duration {
+        Duration::from_nanos(self.busy_nanos.load(Ordering::Relaxed))
+    }
+}
+
+// パイプライン全体の統計
+pub struct PipelineStats {
+    pub read: StageStats,
+    pub chunk: StageStats,
+    pub embed: StageStats,
+    pub upsert: StageStats,
+}
+
+impl Default for PipelineStats {
+    fn default() -> Self {
+        Self {
+            read: StageStats::new("read", "lines"),
+            chunk: StageStats::new("chunk", "chunks"),
+            embed: StageStats::new("embed", "points"),
+            upsert: StageStats::new("upsert", "points"),
+        }
+    }
+}
+
+impl PipelineStats {
+    pub fn stages(&self) -> [&StageStats; 4] {
+        [&self.read, &self.chunk, &self.embed, &self.upsert]
+    }
+}
+
+// パイプラインの実行に必要な依存関係（全ステージで共有）
+pub struct Pipeline {
+    pub client: Qdrant,
+    pub collection_name: String,
+    pub files: Vec<PathBuf>,
+    // ファイルごとの所属テナント
+    pub tenants: Vec<String>,
+    pub config: ProcessingConfig,
+    pub embedders: Embedders,
+    pub schema: PayloadSchema,
+    pub encoding: EncodingConfig,
+    pub normalize: NormalizeConfig,
+    pub context: ContextConfig,
+    pub parents: ParentConfig,
+    pub dedup: DedupConfig,
+    pub failures: FailureConfig,
+    pub dead_letters: DeadLetterWriter,
+    pub checkpoint: CheckpointTracker,
+    pub progress: Arc<Progress>,
+    pub stats: PipelineStats,
+}
+
+impl Pipeline {
+    // ポイントIDとチェックポイントのキーに使うソース名
+    fn source(&self, file: usize) -> String {
+        source_name(&self.files[file])
+    }
+
+    fn tenant(&self, file: usize) -> &str {
+        &self.tenants[file]
+    }
+
+    fn title(&self, file: usize) -> String {
+        self.files[file]
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown")
+            .to_string()
+    }
+
+    // 失敗したチャンクをデッドレターに記録
+    fn dead_letter(
+        &self,
+        stage: &str,
+        file: usize,
+        index: u64,
+        seqs: Vec<u64>,
+        lines: Vec<Line>,
+        error: &anyhow::Error,
+    ) -> Result<()> {
+        // 文脈を付けていない場合は記録しない
+        let contexts = if lines.iter().any(|line| line.context.is_some()) {
+            lines.iter().map(|line| line.context.clone()).collect()
+        } else {
+            Vec::new()
+        };
+        // 親セクションの本文は本文を持つポイントの分のみ記録する
+        let source = self.source(file);
+        let parents = if lines.iter().any(|line| line.section.is_some()) {
+            lines
+                .iter()
+                .zip(&seqs)
+                .map(|(line, &seq)| {
+                    let section = line.section.as_ref()?;
+                    let id = section.parent_id()?.to_string();
+                    let text = (id == point_id(self.tenant(file), &source, seq))
+                        .then(|| section.text.clone());
+                    Some(DeadLetterParent { id, text })
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let neighbors = lines.iter().map(|line| line.neighbors).collect();
+        let lines = lines.into_iter().map(|line| line.text).collect();
+
+        self.dead_letters.write(&DeadLetter {
+            stage: stage.to_string(),
+            collection: self.collection_name.clone(),
+            source,
+            tenant: Some(self.tenant(file).to_string()),
+            title: self.title(file),
+            chunk: index,
+            seqs,
+            lines,
+            contexts,
+            parents,
+            neighbors,
+            error: format!("{:#}", error),
+            failed_at: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+}
+
+pub fn source_name(path: &Path) -> String {
+    path.display().to_string()
+}
+
+// テナント・ソース・行番号から決まる安定したポイントID（再実行しても同じIDになる）
+// 同じソース名を別のテナントが投入しても、互いのポイントを上書きしない
+pub fn point_id(tenant: &str, source: &str, seq: u64) -> String {
+    let name = format!("vectorium:{}:{}#{}", tenant, source, seq);
+    Uuid::new_v5(&Uuid::NAMESPACE_URL, name.as_bytes()).to_string()
+}
+
+// 本文の内容ハッシュ（既存ポイントとの差分判定に使用）
+pub fn content_hash(text: &str) -> String {
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
+
+// 読み込んだ行（原文と埋め込み用の正規化済みテキスト）
+pub struct Line {
+    pub text: String,
+    pub normalized: String,
+    // 埋め込み時に前に付ける文脈（正規化済み、ペイロードの text には含めない）
+    pub context: Option<String>,
+    // 親子チャンクが有効な場合に行を含むセクション
+    pub section: Option<Arc<Section>>,
+    pub neighbors: Neighbors,
+}
+
+// 同じソース内で前後に投入した行の番号（重複除外した行は飛ばす、検索結果を前後の行で広げるために使用）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Neighbors {
+    pub prev: Option<u64>,
+    pub next: Option<u64>,
+}
+
+// チャンク処理（関数型スタイル）
+pub async fn process_chunk(
+    embedders: &Embedders,
+    chunk: &[Line],
+    seqs: &[u64],
+    source: &str,
+    tenant: &str,
+    title: &str,
+    schema: &PayloadSchema,
+) -> Result<ProcessingResult> {
+    // 埋め込みには文脈を付けた正規化済みテキストを使い、ペイロードには原文を残す
+    let texts = chunk
+        .iter()
+        .map(|line| embedding_text(line.context.as_deref(), &line.normalized))
+        .collect();
+    let embeddings = embedders.embed(texts).await?;
+
+    let points: Vec<PointStruct> = embeddings
+        .into_iter()
+        .zip(chunk.iter())
+        .zip(seqs.iter())
+        .map(|((vectors, line), &seq)| {
+            let id = point_id(tenant, source, seq);
+
+            let mut payload = [
+                ("title".to_string(), title.to_string().into()),
+                ("text".to_string(), line.text.clone().into()),
+                ("source".to_string(), source.to_string().into()),
+                ("tenant_id".to_string(), tenant.to_string().into()),
+                ("content_hash".to_string(), content_hash(&line.text).into()),
+                ("seq".to_string(), (seq as i64).into()),
+                ("schema_version".to_string(), SCHEMA_VERSION.into()),
+            ]
+            .into_iter()
+            .collect::<HashMap<String, Value>>();
+
+            // 前後の行のポイントID（IDはソースと行番号から決まる）
+            let neighbors = [("prev", line.neighbors.prev), ("next", line.neighbors.next)];
+            for (name, neighbor) in neighbors {
+                if let Some(neighbor) = neighbor {
+                    payload.insert(format!("{}_seq", name), (neighbor as i64).into());
+                    payload.insert(
+                        format!("{}_id", name),
+                        point_id(tenant, source, neighbor).into(),
+                    );
+                }
+            }
+
+            // 親セクションを参照させ、本文は本文を持つポイントにのみ保存する
+            if let Some(section) = &line.section {
+                payload.extend(section.payload(&id));
+            }
+
+            // スキーマ検証
+            schema
+                .validate(&payload)
+                .with_context(|| format!("Invalid payload for {}#{}", source, seq))?;
+
+            Ok(PointStruct::new(id, vectors, payload))
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(ProcessingResult { points })
+}
+
+// 1リクエストあたりの上限（ポイント数と推定エンコードサイズ）
+#[derive(Debug, Clone, Copy)]
+struct RequestLimits {
+    points: usize,
+    bytes: usize,
+}
+
+// リクエスト内でのポイントのエンコードサイズ（フィールドタグと長さを含む）
+fn encoded_size(point: &PointStruct) -> usize {
+    let len = point.encoded_len();
+    1 + prost::length_delimiter_len(len) + len
+}
+
+impl RequestLimits {
+    fn new(config: &ProcessingConfig) -> Self {
+        Self {
+            points: (config.batch_size * config.chunk_size).max(1),
+            bytes: config.max_request_bytes.max(1),
+        }
+    }
+}
+
+// 上限に収まるようにポイントを分ける（単独で上限を超えるポイントは1件で送る）
+fn request_groups(mut points: Vec<PointStruct>, limits: RequestLimits) -> Vec<Vec<PointStruct>> {
+    let mut starts = Vec::new();
+    let (mut start, mut bytes) = (0, 0);
+
+    for (i, point) in points.iter().enumerate() {
+        let size = encoded_size(point);
+        if i > start && (i - start >= limits.points || bytes + size > limits.bytes) {
+            starts.push(i);
+            (start, bytes) = (i, 0);
+        }
+        bytes += size;
+    }
+
+    // 後ろから切り離して複製せずに分ける
+    let mut groups = Vec::with_capacity(starts.len() + 1);
+    for &start in starts.iter().rev() {
+        groups.push(points.split_off(start));
+    }
+    if !points.is_empty() {
+        groups.push(points);
+    }
+    groups.reverse();
+    groups
+}
+
+// メッセージサイズ超過のエラーか（クライアント側・サーバー側どちらの上限も対象）
+fn is_too_large(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .any(|cause| cause.to_string().to_lowercase().contains("too large"))
+}
+
+// バッチupsert（サイズ超過で拒否された場合は半分に分けて送り直し、失敗時は設定に従って再試行する）
+// 反映を待ってから返す（公開前の検証で数えるポイント数が遅れないように）
+// 失敗した場合も、分けて送り直した分のうち書き込めたポイント数を返す
+async fn upsert_batch(
+    client: &Qdrant,
+    collection_name: &str,
+    points: Vec<PointStruct>,
+    failures: Option<&FailureConfig>,
+) -> (u64, Result<()>) {
+    let retries = failures.map_or(0, FailureConfig::retries);
+    let mut pending = vec![(points, 0)];
+    let mut written = 0;
+
+    while let Some((points, attempt)) = pending.pop() {
+        let count = points.len() as u64;
+        // 分けるか再試行で送り直す可能性がある場合のみ、送信用に複製する
+        let resend = points.len() > 1 || attempt < retries;
+        let (request, kept) = if resend {
+            (points.clone(), Some(points))
+        } else {
+            (points, None)
+        };
+
+        let result = client
+            .upsert_points(UpsertPointsBuilder::new(collection_name, request).wait(true))
+            .await
+            .context("Failed to upsert points");
+        let Err(e) = result else {
+            written += count;
+            continue;
+        };
+
+        match (kept, failures) {
+            (Some(mut points), _) if points.len() > 1 && is_too_large(&e) => {
+                let tail = points.split_off(points.len() / 2);
+                pending.push((tail, attempt));
+                pending.push((points, attempt));
+            }
+            (Some(points), Some(failures)) if attempt < retries => {
+                failures.backoff(attempt).await;
+                pending.push((points, attempt + 1));
+            }
+            _ => return (written, Err(e)),
+        }
+    }
+
+    (written, Ok(()))
+}
+
+// ポイントを上限ごとに分けて順にupsert（パイプライン外からの投入用）
+pub async fn upsert_points(
+    client: &Qdrant,
+    collection_name: &str,
+    points: Vec<PointStruct>,
+    config: &ProcessingConfig,
+) -> Result<()> {
+    for points in request_groups(points, RequestLimits::new(config)) {
+        upsert_batch(client, collection_name, points, None)
+            .await
+            .1?;
+    }
+    Ok(())
+}
+
+// ファイルから非空行を読み取るイテレータ（指定エンコーディングからUTF-8へ変換）
+pub fn read_non_empty_lines(
+    file_path: &std::path::Path,
+    buffer_size: usize,
+    encoding: &'static Encoding,
+) -> Result<impl Iterator<Item = Result<String>>> {
+    let file = File::open(file_path)
+        .with_context(|| format!("Failed to open file: {}", file_path.display()))?;
+
+    let reader = BufReader::with_capacity(buffer_size, DecodingReader::new(file, encoding));
+
+    Ok(reader
+        .lines()
+        .map(|line| line.context("Failed to read line"))
+        .filter_map(|line| match line {
+            Ok(content) if !content.trim().is_empty() => Some(Ok(content)),
+            Ok(_) => None, // 空行をスキップ
+            Err(e) => Some(Err(e)),
+        }))
+}
+
+// 正規化した行を読み取るイテレータ（正規化後に空になる行は除外）
+pub fn read_lines(
+    file_path: &Path,
+    buffer_size: usize,
+    encoding: &'static Encoding,
+    normalize: &NormalizeConfig,
+    context: &ContextConfig,
+) -> Result<impl Iterator<Item = Result<Line>>> {
+    let normalize = normalize.clone();
+    let title = file_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let lines = ContextTracker::new(
+        read_non_empty_lines(file_path, buffer_size, encoding)?,
+        context,
+        &title,
+    );
+
+    // フロントマターは文脈としてのみ使い、行としては投入しない
+    Ok(lines.filter_map(move |line| match line {
+        Ok((text, context)) => {
+            let normalized = normalize.apply(&text);
+            (!normalized.is_empty()).then(|| {
+                Ok(Line {
+                    text,
+                    normalized,
+                    context: context.map(|context| normalize.apply(&context)),
+                    section: None,
+                    neighbors: Neighbors::default(),
+                })
+            })
+        }
+        Err(e) => Some(Err(e)),
+    }))
+}
+
+// 1ファイル分の行を下流へ送る（下流が終了していれば false）
+fn read_file(pipeline: &Pipeline, file: usize, tx: &mpsc::Sender<ReaderMessage>) -> Result<bool> {
+    let progress = &pipeline.progress;
+    progress.file_started(file);
+
+    // デコードできないファイルは報告して次へ進む
+    let encoding = match pipeline.encoding.detect(&pipeline.files[file]) {
+        Ok(encoding) => encoding,
+        Err(e) => {
+            progress.file_undecodable(file, &e);
+            return Ok(true);
+        }
+    };
+
+    let started = Instant::now();
+    let mut count = 0u64;
+    let (mut pending_lines, mut pending_bytes) = (0u64, 0u64);
+
+    let resume_point = pipeline.checkpoint.resume_point(file);
+    let mut lines = Sections::new(
+        read_lines(
+            &pipeline.files[file],
+            pipeline.config.buffer_size,
+            encoding,
+            &pipeline.normalize,
+            &pipeline.context,
+        )?,
+        &pipeline.parents,
+        resume_point.open_section,
+    );
+
+    // 再開時はコミット済みの行を読み飛ばす
+    let resume_lines = resume_point.committed_lines;
+    if resume_lines > 0 {
+        let mut skipped_bytes = 0u64;
+        for line_result in lines.by_ref().take(resume_lines as usize) {
+            skipped_bytes += line_result?.text.len() as u64 + 1;
+        }
+        progress.lines_resumed(file, resume_lines, skipped_bytes);
+    }
+
+    for line_result in lines {
+        let line = line_result?;
+        count += 1;
+        pending_lines += 1;
+        pending_bytes += line.text.len() as u64 + 1;
+        if pending_lines >= PROGRESS_LINE_INTERVAL {
+            progress.lines_read(file, pending_lines, pending_bytes);
+            (pending_lines, pending_bytes) = (0, 0);
+        }
+
+        // 下流が終了している場合はそちらのエラーを優先
+        if tx
+            .blocking_send(ReaderMessage::Line { file, line })
+            .is_err()
+        {
+            return Ok(false);
+        }
+    }
+
+    progress.lines_read(file, pending_lines, pending_bytes);
+    progress.file_read(file);
+    pipeline.stats.read.record(count, started.elapsed());
+
+    Ok(tx.blocking_send(ReaderMessage::EndOfFile { file }).is_ok())
+}
+
+// ファイル読み込みステージ（ブロッキングI/Oのため専用スレッドで実行）
+fn spawn_readers(
+    pipeline: &Arc<Pipeline>,
+    tx: mpsc::Sender<ReaderMessage>,
+) -> Vec<JoinHandle<Result<()>>> {
+    let queue = Arc::new(Mutex::new(
+        (0..pipeline.files.len()).collect::<VecDeque<_>>(),
+    ));
+
+    (0..pipeline.config.readers.max(1))
+        .map(|_| {
+            let pipeline = Arc::clone(pipeline);
+            let queue = Arc::clone(&queue);
+            let tx = tx.clone();
+
+            tokio::task::spawn_blocking(move || {
+                loop {
+                    let next = queue.lock().expect("reader queue poisoned").pop_front();
+                    let Some(file) = next else {
+                        return Ok(());
+                    };
+
+                    let running = read_file(&pipeline, file, &tx)
+                        .inspect_err(|e| pipeline.progress.file_failed(file, e))?;
+                    if !running {
+                        return Ok(());
+                    }
+                }
+            })
+        })
+        .map(|handle| tokio::spawn(async move { handle.await.context("Reader task panicked")? }))
+        .collect()
+}
+
+// チャンクの最後の行の親セクション（再開時に同じ親IDを引き継ぐ）
+fn open_section(lines: &[Line]) -> Option<OpenSection> {
+    lines.last()?.section.as_ref()?.open()
+}
+
+// チャンク化ステージ（ファイルごとに行をまとめ、チャンク番号と行番号を採番）
+fn spawn_chunker(
+    pipeline: &Arc<Pipeline>,
+    mut rx: mpsc::Receiver<ReaderMessage>,
+    tx: mpsc::Sender<Chunk>,
+) -> JoinHandle<Result<Vec<Deduplicator>>> {
+    let pipeline = Arc::clone(pipeline);
+    let chunk_size = pipeline.config.chunk_size.max(1);
+
+    tokio::spawn(async move {
+        let mut cursors: HashMap<usize, FileCursor> = HashMap::new();
+        // 再開時はコミット済みの行との重複は検出できない（状態は永続化しない）
+        let mut dedups = TenantDeduplicators::new(&pipeline.dedup);
+
+        while let Some(message) = rx.recv().await {
+            let started = Instant::now();
+            let (file, end_of_file) = match message {
+                ReaderMessage::Line { file, mut line } => {
+                    let cursor = cursors
+                        .entry(file)
+                        .or_insert_with(|| FileCursor::resume(&pipeline, file));
+                    let seq = cursor.next_seq;
+                    cursor.next_seq += 1;
+                    cursor.consumed += 1;
+
+                    // 重複行はポイントを作らず、残すポイントに位置を記録
+                    if dedups
+                        .check(
+                            pipeline.tenant(file),
+                            Location { file, seq },
+                            &line.normalized,
+                        )
+                        .is_some()
+                    {
+                        pipeline.progress.deduplicated(file, 1);
+                        continue;
+                    }
+                    // セクションで最初に投入する行が親セクションの本文を持つ
+                    if let Some(section) = &line.section {
+                        section.assign(|| {
+                            point_id(pipeline.tenant(file), &pipeline.source(file), seq)
+                        });
+                    }
+
+                    // 前の行とつなぐ（再開直後の最初の行は前の行が分からない）
+                    line.neighbors.prev = cursor.seqs.last().copied();
+                    if let Some(last) = cursor.buffer.last_mut() {
+                        last.neighbors.next = Some(seq);
+                    }
+
+                    cursor.buffer.push(line);
+                    cursor.seqs.push(seq);
+                    // 最後の行は次の行とつなぐまで残すため、1行多くなってから送る
+                    if cursor.buffer.len() <= chunk_size {
+                        continue;
+                    }
+                    (file, false)
+                }
+                ReaderMessage::EndOfFile { file } => (file, true),
+            };
+
+            let cursor = cursors
+                .entry(file)
+                .or_insert_with(|| FileCursor::resume(&pipeline, file));
+            let chunk = cursor.take_chunk(file, end_of_file);
+
+            if end_of_file {
+                let total_chunks = cursor.next_chunk;
+                cursors.remove(&file);
+                // 最終チャンクの送信前に総チャンク数を確定させる
+                pipeline.checkpoint.file_chunked(file, total_chunks)?;
+            }
+
+            let Some(chunk) = chunk else {
+                continue;
+            };
+            pipeline.stats.chunk.record(1, started.elapsed());
+            pipeline.progress.chunk_created(file);
+
+            if tx.send(chunk).await.is_err() {
+                break;
+            }
+        }
+
+        Ok(dedups.into_deduplicators())
+    })
+}
+
+// チャンク化中のファイルの状態
+struct FileCursor {
+    next_chunk: u64,
+    next_seq: u64,
+    // 前回のチャンク以降に消費した行数
+    consumed: u64,
+    seqs: Vec<u64>,
+    buffer: Vec<Line>,
+}
+
+impl FileCursor {
+    // チェックポイントの続きから採番を始める
+    fn resume(pipeline: &Pipeline, file: usize) -> Self {
+        let resume_point = pipeline.checkpoint.resume_point(file);
+        Self {
+            next_chunk: resume_point.committed_chunks,
+            next_seq: resume_point.committed_lines,
+            consumed: 0,
+            seqs: Vec::new(),
+            buffer: Vec::new(),
+        }
+    }
+
+    // ファイルの途中では直前に受け取った最後の行を次のチャンクに残す
+    fn take_chunk(&mut self, file: usize, end_of_file: bool) -> Option<Chunk> {
+        let keep = usize::from(!end_of_file);
+        if self.buffer.len() <= keep {
+            return None;
+        }
+
+        let split = self.buffer.len() - keep;
+        let held_seqs = self.seqs.split_off(split);
+        let held_lines = self.buffer.split_off(split);
+        // 残す行は消費した行のうち最後の1行（送る直前に受け取った行）
+        let consumed = self.consumed - keep as u64;
+        self.consumed = keep as u64;
+        let chunk = Chunk {
+            file,
+            index: self.next_chunk,
+            seqs: std::mem::replace(&mut self.seqs, held_seqs),
+            lines: std::mem::replace(&mut self.buffer, held_lines),
+            consumed,
+        };
+        self.next_chunk += 1;
+        Some(chunk)
+    }
+}
+
+// 埋め込みステージ（複数ワーカーで受信側を共有）
+fn spawn_embedders(
+    pipeline: &Arc<Pipeline>,
+    rx: mpsc::Receiver<Chunk>,
+    tx: mpsc::Sender<EmbeddedChunk>,
+) -> Vec<JoinHandle<Result<()>>> {
+    let rx = Arc::new(tokio::sync::Mutex::new(rx));
+
+    // 各モデルインスタンスに常に仕事があるよう、既定ではインスタンス数だけ起動
+    let workers = match pipeline.config.embed_workers {
+        0 => pipeline.embedders.instances(),
+        n => n,
+    };
+
+    (0..workers.max(1))
+        .map(|_| {
+            let pipeline = Arc::clone(pipeline);
+            let rx = Arc::clone(&rx);
+            let tx = tx.clone();
+
+            tokio::spawn(async move {
+                loop {
+                    let Some(chunk) = rx.lock().await.recv().await else {
+                        return Ok(());
+                    };
+
+                    let started = Instant::now();
+                    let (source, title) = (pipeline.source(chunk.file), pipeline.title(chunk.file));
+                    let result = pipeline
+                        .failures
+                        .retry(|| {
+                            process_chunk(
+                                &pipeline.embedders,
+                                &chunk.lines,
+                                &chunk.seqs,
+                                &source,
+                                pipeline.tenant(chunk.file),
+                                &title,
+                                &pipeline.schema,
+                            )
+                        })
+                        .await;
+
+                    let result = match result {
+                        Ok(result) => result,
+                        Err(e) if pipeline.failures.aborts() => {
+                            pipeline.progress.file_failed(chunk.file, &e);
+                            return Err(e);
+                        }
+                        Err(e) => {
+                            let lines = chunk.seqs.len() as u64;
+                            let section = open_section(&chunk.lines);
+                            pipeline.dead_letter(
+                                "embed",
+                                chunk.file,
+                                chunk.index,
+                                chunk.seqs,
+                                chunk.lines,
+                                &e,
+                            )?;
+                            pipeline.progress.chunk_dead_lettered(chunk.file, lines, &e);
+                            // 記録したチャンクはコミット済みとして扱い、再開時に読み直さない
+                            pipeline.checkpoint.committed(&[(
+                                chunk.file,
+                                chunk.index,
+                                chunk.consumed,
+                                section,
+                            )])?;
+                            continue;
+                        }
+                    };
+                    let count = result.points.len() as u64;
+                    pipeline.stats.embed.record(count, started.elapsed());
+                    pipeline.progress.embedded(chunk.file, count);
+
+                    let embedded = EmbeddedChunk {
+                        file: chunk.file,
+                        index: chunk.index,
+                        consumed: chunk.consumed,
+                        seqs: chunk.seqs,
+                        lines: chunk.lines,
+                        points: result.points,
+                    };
+                    if tx.send(embedded).await.is_err() {
+                        return Ok(());
+                    }
+                }
+            })
+        })
+        .collect()
+}
+
+// upsertステージ（ポイント数かサイズが上限に達するまで蓄積し、上限ごとに分けて送る）
+fn spawn_writers(
+    pipeline: &Arc<Pipeline>,
+    rx: mpsc::Receiver<EmbeddedChunk>,
+) -> Vec<JoinHandle<Result<()>>> {
+    let rx = Arc::new(tokio::sync::Mutex::new(rx));
+    let limits = RequestLimits::new(&pipeline.config);
+
+    (0..pipeline.config.upsert_writers.max(1))
+        .map(|_| {
+            let pipeline = Arc::clone(pipeline);
+            let rx = Arc::clone(&rx);
+
+            tokio::spawn(async move {
+                let mut batch_points = Vec::new();
+                let mut batch_bytes = 0;
+                // チャンクとそのポイントがバッチ内で占める範囲
+                let mut batch_chunks: Vec<(Range<usize>, EmbeddedChunk)> = Vec::new();
+
+                loop {
+                    let received = rx.lock().await.recv().await;
+                    let finished = received.is_none();
+                    if let Some(mut embedded) = received {
+                        batch_bytes += embedded.points.iter().map(encoded_size).sum::<usize>();
+                        let range = batch_points.len()..batch_points.len() + embedded.points.len();
+                        batch_points.append(&mut embedded.points);
+                        batch_chunks.push((range, embedded));
+                    }
+
+                    let full = batch_points.len() >= limits.points || batch_bytes >= limits.bytes;
+                    if !batch_points.is_empty() && (finished || full) {
+                        let started = Instant::now();
+                        let (client, collection_name) =
+                            (&pipeline.client, pipeline.collection_name.as_str());
+
+                        // 失敗したグループの範囲とエラー（スキップする設定では残りのグループも送る）
+                        let mut failed: Vec<(Range<usize>, anyhow::Error)> = Vec::new();
+                        let mut upserted = 0;
+                        let mut start = 0;
+                        for points in request_groups(std::mem::take(&mut batch_points), limits) {
+                            let range = start..start + points.len();
+                            start = range.end;
+                            let (written, result) = upsert_batch(
+                                client,
+                                collection_name,
+                                points,
+                                Some(&pipeline.failures),
+                            )
+                            .await;
+                            upserted += written;
+                            if let Err(e) = result {
+                                failed.push((range, e));
+                                if pipeline.failures.aborts() {
+                                    break;
+                                }
+                            }
+                        }
+                        batch_bytes = 0;
+
+                        // 失敗したバッチでも書き込めたポイントは検証用に計上
+                        pipeline.stats.upsert.record(upserted, started.elapsed());
+                        pipeline.progress.upserted(upserted);
+
+                        if pipeline.failures.aborts() && !failed.is_empty() {
+                            return Err(failed.remove(0).1);
+                        }
+                        // デッドレターへ移す前に、チェックポイントに残す最後の行の親セクションを控える
+                        let committed: Vec<_> = batch_chunks
+                            .iter()
+                            .map(|(_, chunk)| {
+                                let section = open_section(&chunk.lines);
+                                (chunk.file, chunk.index, chunk.consumed, section)
+                            })
+                            .collect();
+                        // 失敗したグループにポイントを含むチャンクのみ記録して続行
+                        for (range, chunk) in &mut batch_chunks {
+                            let Some((_, e)) = failed.iter().find(|(group, _)| {
+                                group.start < range.end && range.start < group.end
+                            }) else {
+                                continue;
+                            };
+                            pipeline.dead_letter(
+                                "upsert",
+                                chunk.file,
+                                chunk.index,
+                                std::mem::take(&mut chunk.seqs),
+                                std::mem::take(&mut chunk.lines),
+                                e,
+                            )?;
+                            // 行は埋め込み済みとして計上済み
+                            pipeline.progress.chunk_dead_lettered(chunk.file, 0, e);
+                        }
+
+                        // upsert成功後（または記録後）にチェックポイントを保存
+                        batch_chunks.clear();
+                        pipeline.checkpoint.committed(&committed)?;
+                    }
+
+                    if finished {
+                        return Ok(());
+                    }
+                }
+            })
+        })
+        .collect()
+}
+
+// 全タスクの完了を待ち、最初のエラーを返す
+async fn join_all<T>(handles: Vec<JoinHandle<Result<T>>>) -> Result<Vec<T>> {
+    let mut values = Vec::with_capacity(handles.len());
+    let mut first_error = None;
+
+    for handle in handles {
+        match handle.await {
+            Ok(Ok(value)) => values.push(value),
+            Ok(Err(e)) => {
+                first_error.get_or_insert(e);
+            }
+            Err(e) => {
+                first_error.get_or_insert(anyhow!(e).context("Pipeline task panicked"));
+            }
+        }
+    }
+
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(values),
+    }
+}
+
+// 読み込み → チャンク化 → 埋め込み → upsert のパイプラインを実行
+pub async fn run(pipeline: &Arc<Pipeline>) -> Result<()> {
+    let queue_depth = pipeline.config.queue_depth.max(1);
+
+    // 有界チャネルで背圧をかける
+    let (line_tx, line_rx) = mpsc::channel(queue_depth * pipeline.config.chunk_size.max(1));
+    let (chunk_tx, chunk_rx) = mpsc::channel(queue_depth);
+    let (point_tx, point_rx) = mpsc::channel(queue_depth);
+
+    let readers = spawn_readers(pipeline, line_tx);
+    let chunker = spawn_chunker(pipeline, line_rx, chunk_tx);
+    let embedders = spawn_embedders(pipeline, chunk_rx, point_tx);
+    let writers = spawn_writers(pipeline, point_rx);
+
+    // 失敗したステージ以外は送受信の切断で静かに停止するため、全ステージを待ってから判定
+    let writers_result = join_all(writers).await;
+    let embedders_result = join_all(embedders).await;
+    let chunker_result = join_all(vec![chunker]).await;
+    let readers_result = join_all(readers).await;
+
+    readers_result?;
+    let dedups = chunker_result?.pop().unwrap_or_default();
+    embedders_result?;
+    writers_result?;
+
+    // 全ポイントのupsert後に、重複元の位置を残したポイントへ記録
+    for dedup in dedups {
+        record_merged(pipeline, dedup.into_merged()).await?;
+    }
+
+    Ok(())
+}
+
+// 重複の記録を1回の更新でまとめて書き込む件数
+const MERGED_BATCH_SIZE: usize = 256;
+
+// 除外した重複行の位置と件数（残したポイントのIDごと）
+pub struct MergedRecord {
+    pub id: String,
+    pub locations: Vec<String>,
+    pub count: u64,
+}
+
+// 除外した重複行の位置と件数を残したポイントのペイロードに追記（まとめて更新する）
+pub async fn set_merged(
+    client: &Qdrant,
+    collection_name: &str,
+    records: &[MergedRecord],
+) -> Result<()> {
+    for batch in records.chunks(MERGED_BATCH_SIZE) {
+        let operations: Vec<PointsUpdateOperation> = batch
+            .iter()
+            .map(|record| PointsUpdateOperation {
+                operation: Some(Operation::SetPayload(SetPayload {
+                    payload: [
+                        (
+                            "merged_sources".to_string(),
+                            record.locations.clone().into(),
+                        ),
+                        ("duplicate_count".to_string(), (record.count as i64).into()),
+                    ]
+                    .into_iter()
+                    .collect::<HashMap<String, Value>>(),
+                    points_selector: Some(vec![PointId::from(record.id.clone())].into()),
+                    ..Default::default()
+                })),
+            })
+            .collect();
+
+        client
+            .update_points_batch(
+                UpdateBatchPointsBuilder::new(collection_name, operations).wait(true),
+            )
+            .await
+            .context("Failed to record merged duplicates")?;
+    }
+    Ok(())
+}
+
+// 除外した重複行の位置を残したポイントのペイロードに追記
+async fn record_merged(pipeline: &Pipeline, merged: HashMap<Location, Merged>) -> Result<()> {
+    let (survivors, records): (Vec<Location>, Vec<MergedRecord>) = merged
+        .into_iter()
+        .map(|(survivor, merged)| {
+            let locations = merged
+                .locations
+                .iter()
+                .map(|location| format!("{}#{}", pipeline.source(location.file), location.seq))
+                .collect();
+            let id = point_id(
+                pipeline.tenant(survivor.file),
+                &pipeline.source(survivor.file),
+                survivor.seq,
+            );
+            let record = MergedRecord {
+                id,
+                locations,
+                count: merged.count,
+            };
+            (survivor, record)
+        })
+        .unzip();
+
+    for (survivors, records) in survivors
+        .chunks(MERGED_BATCH_SIZE)
+        .zip(records.chunks(MERGED_BATCH_SIZE))
+    {
+        match set_merged(&pipeline.client, &pipeline.collection_name, records).await {
+            Err(_) if !pipeline.failures.aborts() => {}
+            result => {
+                result?;
+                continue;
+            }
+        }
+
+        // 残したポイントのチャンクがデッドレターに回った場合は記録先がないため、1件ずつ書き直す
+        for (survivor, record) in survivors.iter().zip(records) {
+            let result = set_merged(
+                &pipeline.client,
+                &pipeline.collection_name,
+                std::slice::from_ref(record),
+            )
+            .await;
+            if let Err(e) = result {
+                pipeline.progress.log(&format!(
+                    "Skipped merged duplicates of {}#{}: {:#}",
+                    pipeline.source(survivor.file),
+                    survivor.seq,
+                    e
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qdrant_client::Payload;
+
+    #[test]
+    fn point_id_is_stable() {
+        assert_eq!(
+            point_id("default", "data/a.md", 3),
+            point_id("default", "data/a.md", 3)
+        );
+        assert_ne!(
+            point_id("default", "data/a.md", 3),
+            point_id("default", "data/a.md", 4)
+        );
+    }
+
+    #[test]
+    fn same_source_in_two_tenants_keeps_both_points() {
+        // upsertと同じくIDで重ね合わせ、後から投入したテナントが上書きしないことを確認
+        let mut points: HashMap<String, &str> = HashMap::new();
+        for tenant in ["acme", "globex"] {
+            for seq in 0..3 {
+                points.insert(point_id(tenant, "text:notes", seq), tenant);
+            }
+        }
+
+        assert_eq!(points.len(), 6);
+        for tenant in ["acme", "globex"] {
+            assert_eq!(points.values().filter(|&&t| t == tenant).count(), 3);
+        }
+    }
+
+    #[test]
+    fn request_groups_keep_order_within_limits() {
+        let points: Vec<PointStruct> = (0..7u64)
+            .map(|seq| PointStruct::new(seq, vec![0.0f32; 4], Payload::new()))
+            .collect();
+        let limits = RequestLimits {
+            points: 3,
+            bytes: usize::MAX,
+        };
+
+        let groups = request_groups(points, limits);
+        let sizes: Vec<usize> = groups.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [3, 3, 1]);
+        let ids: Vec<_> = groups.into_iter().flatten().map(|point| point.id).collect();
+        let expected: Vec<_> = (0..7u64).map(|seq| Some(seq.into())).collect();
+        assert_eq!(ids, expected);
+    }
+}
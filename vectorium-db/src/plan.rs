@@ -0,0 +1,449 @@
+use anyhow::{Context, Result};
+use qdrant_client::Qdrant;
+use qdrant_client::qdrant::point_id::PointIdOptions;
+use qdrant_client::qdrant::value::Kind;
+use qdrant_client::qdrant::{PayloadIncludeSelector, PointId, ScrollPointsBuilder, Value};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use vectorium_common::EmbeddingModel;
+
+use crate::collections;
+use crate::config::Config;
+use crate::context::embedding_text;
+use crate::dedup::{Location, TenantDeduplicators};
+use crate::migrate::is_migrated_source;
+use crate::pipeline::{content_hash, point_id, read_lines, source_name};
+use crate::progress::{OutputMode, format_duration};
+use crate::text::is_loader_source;
+
+// 既存ポイントを取得する際の1ページあたりの件数
+const SCROLL_PAGE_SIZE: u32 = 1000;
+
+// ファイルごとの投入計画
+#[derive(Debug, Default, Serialize)]
+pub struct FilePlan {
+    pub source: String,
+    pub tenant: String,
+    // 判定したエンコーディング
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    pub bytes: u64,
+    pub lines: u64,
+    // 重複として除外される行数
+    pub duplicates: u64,
+    pub chunks: u64,
+    pub tokens: u64,
+    pub added: u64,
+    pub updated: u64,
+    pub unchanged: u64,
+    // 読み込みに失敗した場合のエラー
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// 既存コレクションとの差分
+#[derive(Debug, Default, Serialize)]
+pub struct PlanDiff {
+    pub collection_exists: bool,
+    pub existing: u64,
+    pub added: u64,
+    pub updated: u64,
+    pub unchanged: u64,
+    pub deleted: u64,
+    // ローダー（text/mail/chat/git）のポイントは新しいバージョンへ引き継ぐ
+    pub carried: u64,
+    // 移行時に元のファイルを特定できなかったポイント（削除対象には含めない）
+    pub migrated: u64,
+    // 削除されるポイントのソース別件数
+    pub deleted_sources: BTreeMap<String, u64>,
+}
+
+// ドライランの結果
+#[derive(Debug, Serialize)]
+pub struct Plan {
+    pub collection: String,
+    pub files: Vec<FilePlan>,
+    pub total_files: usize,
+    pub total_lines: u64,
+    pub total_duplicates: u64,
+    pub total_chunks: u64,
+    pub total_tokens: u64,
+    // 埋め込むモデル（名前付きベクトル）
+    pub models: Vec<String>,
+    pub embedding_instances: usize,
+    pub estimated_embedding_secs: f64,
+    // Qdrantに接続できない場合は差分なし
+    pub diff: Option<PlanDiff>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff_error: Option<String>,
+}
+
+// 投入予定のポイント（ファイル番号と内容ハッシュ）
+struct PlannedPoint {
+    file: usize,
+    hash: String,
+}
+
+// 既存ポイントのペイロード（差分判定に必要な項目のみ）
+struct ExistingPoint {
+    source: Option<String>,
+    hash: Option<String>,
+}
+
+// ソースを走査してチャンク化し、既存ポイントとの差分を見積もる（モデル読み込みや書き込みは行わない）
+pub async fn build(
+    client: &Qdrant,
+    files: Vec<(PathBuf, String)>,
+    config: &Config,
+    models: &[EmbeddingModel],
+) -> Result<Plan> {
+    let scan_config = config.clone();
+    let (mut file_plans, mut planned) =
+        tokio::task::spawn_blocking(move || scan_files(&files, &scan_config))
+            .await
+            .context("Plan scan panicked")?;
+
+    let (diff, diff_error) = match fetch_existing(client, &config.collection).await {
+        Ok(existing) => (Some(compare(&mut file_plans, &mut planned, existing)), None),
+        Err(e) => (None, Some(format!("{:#}", e))),
+    };
+
+    let total_lines = file_plans.iter().map(|plan| plan.lines).sum::<u64>();
+    let total_duplicates = file_plans.iter().map(|plan| plan.duplicates).sum::<u64>();
+    let embedding_instances = config.embedding.resolved_instances();
+    let sentences_per_sec = config.plan.sentences_per_sec.max(f64::EPSILON);
+
+    Ok(Plan {
+        collection: config.collection.clone(),
+        total_files: file_plans.len(),
+        total_lines,
+        total_duplicates,
+        total_chunks: file_plans.iter().map(|plan| plan.chunks).sum(),
+        total_tokens: file_plans.iter().map(|plan| plan.tokens).sum(),
+        models: models
+            .iter()
+            .map(|model| model.name().to_string())
+            .collect(),
+        embedding_instances,
+        // モデルごとに全行を埋め込む
+        estimated_embedding_secs: (total_lines - total_duplicates) as f64 * models.len() as f64
+            / (sentences_per_sec * embedding_instances as f64),
+        files: file_plans,
+        diff,
+        diff_error,
+    })
+}
+
+// 全ファイルを読み、投入予定のポイントIDと内容ハッシュを集める
+fn scan_files(
+    files: &[(PathBuf, String)],
+    config: &Config,
+) -> (Vec<FilePlan>, HashMap<String, PlannedPoint>) {
+    let chunk_size = config.processing.chunk_size.max(1) as u64;
+    let buffer_size = config.processing.buffer_size;
+    let mut planned = HashMap::new();
+    // 重複排除はテナントごと（インジェストと同じ）
+    let mut dedups = TenantDeduplicators::new(&config.dedup);
+
+    let file_plans = files
+        .iter()
+        .enumerate()
+        .map(|(file, (path, tenant))| {
+            let mut plan = FilePlan {
+                source: source_name(path),
+                tenant: tenant.clone(),
+                ..FilePlan::default()
+            };
+
+            // デコードできないファイルはエラーとして計画に記録
+            let result = config
+                .encoding
+                .detect(path)
+                .and_then(|encoding| {
+                    plan.encoding = Some(encoding.name().to_string());
+                    read_lines(
+                        path,
+                        buffer_size,
+                        encoding,
+                        &config.normalize,
+                        &config.context,
+                    )
+                })
+                .and_then(|lines| {
+                    for line_result in lines {
+                        let line = line_result?;
+                        let seq = plan.lines;
+                        plan.lines += 1;
+                        plan.bytes += line.text.len() as u64 + 1;
+
+                        if dedups
+                            .check(tenant, Location { file, seq }, &line.normalized)
+                            .is_some()
+                        {
+                            plan.duplicates += 1;
+                            continue;
+                        }
+
+                        planned.insert(
+                            point_id(&plan.tenant, &plan.source, seq),
+                            PlannedPoint {
+                                file,
+                                hash: content_hash(&line.text),
+                            },
+                        );
+                        // 文脈を付ける場合はその分も埋め込む
+                        plan.tokens += estimate_tokens(&embedding_text(
+                            line.context.as_deref(),
+                            &line.normalized,
+                        ));
+                    }
+                    Ok(())
+                });
+
+            if let Err(e) = result {
+                plan.error = Some(format!("{:#}", e));
+            }
+            plan.chunks = (plan.lines - plan.duplicates).div_ceil(chunk_size);
+            plan
+        })
+        .collect();
+
+    (file_plans, planned)
+}
+
+// トークン数の概算（英数字は単語あたり約1.3、それ以外の文字は1文字1トークン）
+fn estimate_tokens(text: &str) -> u64 {
+    let mut words = 0u64;
+    let mut others = 0u64;
+    let mut in_word = false;
+
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() {
+            if !in_word {
+                words += 1;
+            }
+            in_word = true;
+        } else {
+            in_word = false;
+            if !c.is_whitespace() && !c.is_ascii_punctuation() {
+                others += 1;
+            }
+        }
+    }
+
+    (words * 4).div_ceil(3) + others
+}
+
+// 既存ポイントのIDとペイロードを読み取り専用で取得
+async fn fetch_existing(
+    client: &Qdrant,
+    collection_name: &str,
+) -> Result<Option<HashMap<String, ExistingPoint>>> {
+    // エイリアスの場合は参照先の実コレクションを読む
+    let Some(collection_name) = collections::resolve(client, collection_name).await? else {
+        return Ok(None);
+    };
+
+    let mut existing = HashMap::new();
+    let mut offset: Option<PointId> = None;
+
+    loop {
+        let mut request = ScrollPointsBuilder::new(&collection_name)
+            .limit(SCROLL_PAGE_SIZE)
+            .with_payload(PayloadIncludeSelector {
+                fields: vec!["source".to_string(), "content_hash".to_string()],
+            })
+            .with_vectors(false);
+        if let Some(offset) = offset.take() {
+            request = request.offset(offset);
+        }
+
+        let response = client
+            .scroll(request)
+            .await
+            .context("Failed to scroll existing points")?;
+
+        for point in response.result {
+            let Some(id) = point.id.as_ref().and_then(point_id_string) else {
+                continue;
+            };
+            let existing_point = ExistingPoint {
+                source: string_value(point.payload.get("source")),
+                hash: string_value(point.payload.get("content_hash")),
+            };
+            existing.insert(id, existing_point);
+        }
+
+        match response.next_page_offset {
+            Some(next) => offset = Some(next),
+            None => break,
+        }
+    }
+
+    Ok(Some(existing))
+}
+
+// 投入予定と既存ポイントを突き合わせる
+fn compare(
+    file_plans: &mut [FilePlan],
+    planned: &mut HashMap<String, PlannedPoint>,
+    existing: Option<HashMap<String, ExistingPoint>>,
+) -> PlanDiff {
+    let mut diff = PlanDiff {
+        collection_exists: existing.is_some(),
+        ..PlanDiff::default()
+    };
+
+    for (id, existing_point) in existing.unwrap_or_default() {
+        diff.existing += 1;
+        match planned.remove(&id) {
+            Some(point) if existing_point.hash.as_deref() == Some(point.hash.as_str()) => {
+                diff.unchanged += 1;
+                file_plans[point.file].unchanged += 1;
+            }
+            Some(point) => {
+                diff.updated += 1;
+                file_plans[point.file].updated += 1;
+            }
+            None if existing_point
+                .source
+                .as_deref()
+                .is_some_and(is_loader_source) =>
+            {
+                diff.carried += 1;
+            }
+            None if existing_point
+                .source
+                .as_deref()
+                .is_some_and(is_migrated_source) =>
+            {
+                diff.migrated += 1;
+            }
+            None => {
+                diff.deleted += 1;
+                let source = existing_point
+                    .source
+                    .unwrap_or_else(|| "(unknown)".to_string());
+                *diff.deleted_sources.entry(source).or_default() += 1;
+            }
+        }
+    }
+
+    // 既存に対応するIDがないものは新規
+    for point in planned.values() {
+        diff.added += 1;
+        file_plans[point.file].added += 1;
+    }
+
+    diff
+}
+
+fn point_id_string(id: &PointId) -> Option<String> {
+    match id.point_id_options.as_ref()? {
+        PointIdOptions::Uuid(uuid) => Some(uuid.clone()),
+        PointIdOptions::Num(num) => Some(num.to_string()),
+    }
+}
+
+fn string_value(value: Option<&Value>) -> Option<String> {
+    match value?.kind.as_ref()? {
+        Kind::StringValue(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+impl Plan {
+    pub fn print(&self, mode: OutputMode) {
+        if mode == OutputMode::Json {
+            match serde_json::to_string(self) {
+                Ok(json) => println!("{}", json),
+                Err(e) => eprintln!("Failed to serialize plan: {}", e),
+            }
+            return;
+        }
+
+        println!("=== Dry run: {} ===", self.collection);
+        println!(
+            "{:<40} {:<12} {:<12} {:>10} {:>10} {:>8} {:>10} {:>8} {:>8} {:>9}",
+            "source",
+            "tenant",
+            "encoding",
+            "lines",
+            "duplicates",
+            "chunks",
+            "tokens",
+            "added",
+            "updated",
+            "unchanged"
+        );
+        for file in &self.files {
+            match &file.error {
+                Some(error) => println!("{:<40} error: {}", file.source, error),
+                None => println!(
+                    "{:<40} {:<12} {:<12} {:>10} {:>10} {:>8} {:>10} {:>8} {:>8} {:>9}",
+                    file.source,
+                    file.tenant,
+                    file.encoding.as_deref().unwrap_or("-"),
+                    file.lines,
+                    file.duplicates,
+                    file.chunks,
+                    file.tokens,
+                    file.added,
+                    file.updated,
+                    file.unchanged
+                ),
+            }
+        }
+
+        let rows = [
+            ("files", self.total_files.to_string()),
+            ("lines", self.total_lines.to_string()),
+            ("duplicates", self.total_duplicates.to_string()),
+            ("chunks", self.total_chunks.to_string()),
+            ("tokens", format!("~{}", self.total_tokens)),
+            ("models", self.models.join(", ")),
+            ("instances", self.embedding_instances.to_string()),
+            (
+                "embedding",
+                format!(
+                    "~{}",
+                    format_duration(Duration::from_secs_f64(self.estimated_embedding_secs))
+                ),
+            ),
+        ];
+
+        println!("--- Totals ---");
+        for (label, value) in rows {
+            println!("{:<10} {:>12}", label, value);
+        }
+
+        println!("--- Changes ---");
+        match (&self.diff, &self.diff_error) {
+            (Some(diff), _) => {
+                if !diff.collection_exists {
+                    println!("collection does not exist; all points will be added");
+                }
+                let rows = [
+                    ("existing", diff.existing),
+                    ("added", diff.added),
+                    ("updated", diff.updated),
+                    ("unchanged", diff.unchanged),
+                    ("deleted", diff.deleted),
+                    ("carried", diff.carried),
+                    ("migrated", diff.migrated),
+                ];
+                for (label, value) in rows {
+                    println!("{:<10} {:>12}", label, value);
+                }
+                for (source, count) in &diff.deleted_sources {
+                    println!("  deleted {:>8} from {}", count, source);
+                }
+            }
+            (None, Some(error)) => println!("unavailable: {}", error),
+            (None, None) => {}
+        }
+    }
+}
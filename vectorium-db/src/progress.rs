@@ -0,0 +1,636 @@
+use console::{Term, style, truncate_str};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::pipeline::PipelineStats;
+
+// 描画間隔
+const RENDER_INTERVAL: Duration = Duration::from_millis(150);
+// 進捗バーの幅（文字数）
+const BAR_WIDTH: usize = 30;
+// 同時に表示するファイル別バーの上限
+const MAX_FILE_BARS: usize = 8;
+
+// 出力モード
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    // 端末向けの進捗バー
+    Interactive,
+    // 端末以外への行単位ログ
+    Plain,
+    // 最終サマリーのみ
+    Quiet,
+    // 最終サマリーをJSONで出力
+    Json,
+}
+
+impl OutputMode {
+    pub fn detect(quiet: bool, json: bool) -> Self {
+        if json {
+            OutputMode::Json
+        } else if quiet {
+            OutputMode::Quiet
+        } else if Term::stderr().is_term() {
+            OutputMode::Interactive
+        } else {
+            OutputMode::Plain
+        }
+    }
+}
+
+// ファイルごとの進捗
+struct FileProgress {
+    name: String,
+    total_bytes: u64,
+    bytes_read: u64,
+    lines_read: u64,
+    embedded: u64,
+    reading_done: bool,
+}
+
+impl FileProgress {
+    // 読み込み済みバイト比から総行数を推定し、埋め込み済み割合を返す
+    fn ratio(&self) -> f64 {
+        let estimated_lines = if self.reading_done {
+            self.lines_read as f64
+        } else if self.bytes_read > 0 {
+            self.lines_read as f64 * self.total_bytes as f64 / self.bytes_read as f64
+        } else {
+            0.0
+        };
+
+        if estimated_lines > 0.0 {
+            (self.embedded as f64 / estimated_lines).min(1.0)
+        } else {
+            0.0
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.reading_done && self.embedded >= self.lines_read
+    }
+}
+
+#[derive(Default)]
+struct State {
+    active: BTreeMap<usize, FileProgress>,
+    total_files: u64,
+    total_bytes: u64,
+    bytes_read: u64,
+    lines_read: u64,
+    chunks: u64,
+    embedded: u64,
+    resumed: u64,
+    duplicates: u64,
+    upserted: u64,
+    files_done: u64,
+    skipped: u64,
+    failed: u64,
+    dead_lettered: u64,
+    undecodable: Vec<UndecodableFile>,
+    // ファイルごとの結果と処理開始時刻（レポート用）
+    files: Vec<FileReport>,
+    started_at: Vec<Option<Instant>>,
+    drawn_lines: usize,
+}
+
+impl State {
+    // ファイルの処理を終え、所要時間を記録
+    fn finish_file(&mut self, file: usize, status: FileStatus) {
+        let report = &mut self.files[file];
+        report.status = status;
+        report.duration_secs = self.started_at[file].map(|started| started.elapsed().as_secs_f64());
+    }
+}
+
+// 進捗の集計と表示
+pub struct Progress {
+    mode: OutputMode,
+    started: Instant,
+    term: Term,
+    sizes: Vec<u64>,
+    names: Vec<String>,
+    state: Mutex<State>,
+}
+
+impl Progress {
+    pub fn new(mode: OutputMode, file_paths: &[PathBuf]) -> Arc<Self> {
+        let sizes: Vec<u64> = file_paths
+            .iter()
+            .map(|path| std::fs::metadata(path).map(|m| m.len()).unwrap_or(0))
+            .collect();
+        let names: Vec<String> = file_paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+
+        let state = State {
+            total_files: file_paths.len() as u64,
+            total_bytes: sizes.iter().sum(),
+            files: names
+                .iter()
+                .map(|name| FileReport {
+                    file: name.clone(),
+                    ..FileReport::default()
+                })
+                .collect(),
+            started_at: vec![None; file_paths.len()],
+            ..State::default()
+        };
+
+        Arc::new(Self {
+            mode,
+            started: Instant::now(),
+            term: Term::stderr(),
+            sizes,
+            names,
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn mode(&self) -> OutputMode {
+        self.mode
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("progress state poisoned")
+    }
+
+    // 進捗表示を崩さずにメッセージを出力
+    pub fn log(&self, message: &str) {
+        match self.mode {
+            OutputMode::Interactive => {
+                let mut state = self.state();
+                let _ = self.term.clear_last_lines(state.drawn_lines);
+                state.drawn_lines = 0;
+                let _ = self.term.write_line(message);
+            }
+            OutputMode::Plain => println!("{}", message),
+            OutputMode::Quiet | OutputMode::Json => {}
+        }
+    }
+
+    pub fn file_started(&self, file: usize) {
+        let mut state = self.state();
+        state.files[file].status = FileStatus::Incomplete;
+        state.started_at[file] = Some(Instant::now());
+        state.active.insert(
+            file,
+            FileProgress {
+                name: self.names[file].clone(),
+                total_bytes: self.sizes[file],
+                bytes_read: 0,
+                lines_read: 0,
+                embedded: 0,
+                reading_done: false,
+            },
+        );
+        drop(state);
+
+        if self.mode == OutputMode::Plain {
+            println!("Processing file: {}", self.names[file]);
+        }
+    }
+
+    pub fn lines_read(&self, file: usize, lines: u64, bytes: u64) {
+        let mut state = self.state();
+        state.lines_read += lines;
+        state.bytes_read += bytes;
+        state.files[file].lines += lines;
+        state.files[file].bytes += bytes;
+        if let Some(progress) = state.active.get_mut(&file) {
+            progress.lines_read += lines;
+            progress.bytes_read += bytes;
+        }
+    }
+
+    // 前回の実行でコミット済みの行（読み込み・埋め込み済みとして扱う）
+    pub fn lines_resumed(&self, file: usize, lines: u64, bytes: u64) {
+        let mut state = self.state();
+        state.lines_read += lines;
+        state.bytes_read += bytes;
+        state.resumed += lines;
+        state.files[file].lines += lines;
+        state.files[file].bytes += bytes;
+        state.files[file].resumed_lines += lines;
+        if let Some(progress) = state.active.get_mut(&file) {
+            progress.lines_read += lines;
+            progress.bytes_read += bytes;
+            progress.embedded += lines;
+        }
+    }
+
+    // 読み込み完了（非空行がなければスキップ扱い）
+    pub fn file_read(&self, file: usize) {
+        let mut state = self.state();
+        let Some(progress) = state.active.get_mut(&file) else {
+            return;
+        };
+
+        progress.reading_done = true;
+        let remaining = progress.total_bytes.saturating_sub(progress.bytes_read);
+        progress.bytes_read = progress.total_bytes;
+        let empty = progress.lines_read == 0;
+        // 再開時は全行がコミット済みの場合がある
+        let complete = progress.is_complete();
+        state.bytes_read += remaining;
+        state.files[file].bytes += remaining;
+
+        if empty {
+            state.active.remove(&file);
+            state.skipped += 1;
+            state.files[file].skipped_reason = Some("empty".to_string());
+            state.finish_file(file, FileStatus::Skipped);
+            drop(state);
+            if self.mode == OutputMode::Plain {
+                println!("Skipped empty file: {}", self.names[file]);
+            }
+        } else if complete {
+            state.active.remove(&file);
+            state.files_done += 1;
+            state.finish_file(file, FileStatus::Completed);
+        }
+    }
+
+    pub fn file_failed(&self, file: usize, error: &anyhow::Error) {
+        let mut state = self.state();
+        if state.active.remove(&file).is_some() {
+            state.failed += 1;
+        }
+        state.files[file].errors.push(format!("{:#}", error));
+        state.finish_file(file, FileStatus::Failed);
+        drop(state);
+
+        self.log(&format!(
+            "Failed to process {}: {:#}",
+            self.names[file], error
+        ));
+    }
+
+    // エンコーディングを判定できずスキップしたファイル
+    pub fn file_undecodable(&self, file: usize, error: &anyhow::Error) {
+        let mut state = self.state();
+        state.active.remove(&file);
+        state.skipped += 1;
+        state.undecodable.push(UndecodableFile {
+            file: self.names[file].clone(),
+            reason: format!("{:#}", error),
+        });
+        state.files[file].skipped_reason = Some(format!("undecodable: {:#}", error));
+        state.finish_file(file, FileStatus::Skipped);
+        drop(state);
+
+        self.log(&format!(
+            "Skipped undecodable file {}: {:#}",
+            self.names[file], error
+        ));
+    }
+
+    pub fn chunk_created(&self, file: usize) {
+        let mut state = self.state();
+        state.chunks += 1;
+        state.files[file].chunks += 1;
+    }
+
+    pub fn embedded(&self, file: usize, points: u64) {
+        let mut state = self.state();
+        state.embedded += points;
+        state.files[file].points += points;
+        self.lines_done(state, file, points);
+    }
+
+    // 重複として除外した行（埋め込み済みとして扱う）
+    pub fn deduplicated(&self, file: usize, lines: u64) {
+        let mut state = self.state();
+        state.duplicates += lines;
+        state.files[file].duplicates += lines;
+        self.lines_done(state, file, lines);
+    }
+
+    // デッドレターに記録したチャンク（未計上の行は処理済みとして扱う）
+    pub fn chunk_dead_lettered(&self, file: usize, lines: u64, error: &anyhow::Error) {
+        let mut state = self.state();
+        state.dead_lettered += 1;
+        state.files[file].dead_lettered += 1;
+        state.files[file].errors.push(format!("{:#}", error));
+        self.lines_done(state, file, lines);
+
+        self.log(&format!(
+            "Dead-lettered a chunk of {}: {:#}",
+            self.names[file], error
+        ));
+    }
+
+    fn lines_done(&self, mut state: std::sync::MutexGuard<'_, State>, file: usize, lines: u64) {
+        let complete = match state.active.get_mut(&file) {
+            Some(progress) => {
+                progress.embedded += lines;
+                progress.is_complete()
+            }
+            None => false,
+        };
+
+        if complete {
+            state.active.remove(&file);
+            state.files_done += 1;
+            state.finish_file(file, FileStatus::Completed);
+            drop(state);
+            if self.mode == OutputMode::Plain {
+                println!("Completed processing file: {}", self.names[file]);
+            }
+        }
+    }
+
+    pub fn upserted(&self, points: u64) {
+        self.state().upserted += points;
+    }
+
+    // 定期的に進捗バーを再描画するタスクを起動
+    pub fn spawn_renderer(self: &Arc<Self>) -> Renderer {
+        let (stop_tx, mut stop_rx) = watch::channel(false);
+        let progress = Arc::clone(self);
+
+        let handle = tokio::spawn(async move {
+            if progress.mode != OutputMode::Interactive {
+                return;
+            }
+
+            let _ = progress.term.hide_cursor();
+            let mut interval = tokio::time::interval(RENDER_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => progress.render(),
+                    _ = stop_rx.changed() => break,
+                }
+            }
+
+            let mut state = progress.state();
+            let _ = progress.term.clear_last_lines(state.drawn_lines);
+            state.drawn_lines = 0;
+            let _ = progress.term.show_cursor();
+        });
+
+        Renderer { stop_tx, handle }
+    }
+
+    fn render(&self) {
+        let mut state = self.state();
+        let elapsed = self.started.elapsed();
+        let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+        let (width, _) = self.term.size();
+        let width = width as usize;
+
+        let lines_per_sec = (state.lines_read - state.resumed) as f64 / secs;
+        let embeddings_per_sec = state.embedded as f64 / secs;
+
+        // 読み込み済みの行密度から総行数を推定してETAを算出
+        let estimated_lines = if state.bytes_read > 0 {
+            state.lines_read as f64 * state.total_bytes as f64 / state.bytes_read as f64
+        } else {
+            0.0
+        };
+        let done_lines = (state.embedded + state.resumed + state.duplicates) as f64;
+        let overall_ratio = if estimated_lines > 0.0 {
+            (done_lines / estimated_lines).min(1.0)
+        } else {
+            0.0
+        };
+        let eta = if embeddings_per_sec > 0.0 && estimated_lines > 0.0 {
+            format_duration(Duration::from_secs_f64(
+                (estimated_lines - done_lines).max(0.0) / embeddings_per_sec,
+            ))
+        } else {
+            "--:--".to_string()
+        };
+
+        let mut lines = vec![format!(
+            "{} {} {:>3}%  files {}/{}  {:.0} lines/s  {:.0} emb/s  elapsed {}  ETA {}",
+            style("total").bold(),
+            bar(overall_ratio),
+            (overall_ratio * 100.0) as u32,
+            state.files_done,
+            state.total_files,
+            lines_per_sec,
+            embeddings_per_sec,
+            format_duration(elapsed),
+            eta
+        )];
+
+        lines.extend(state.active.values().take(MAX_FILE_BARS).map(|file| {
+            format!(
+                "  {} {:>3}%  {}",
+                bar(file.ratio()),
+                (file.ratio() * 100.0) as u32,
+                style(&file.name).dim()
+            )
+        }));
+        if state.active.len() > MAX_FILE_BARS {
+            lines.push(format!(
+                "  … {} more files",
+                state.active.len() - MAX_FILE_BARS
+            ));
+        }
+
+        let _ = self.term.clear_last_lines(state.drawn_lines);
+        for line in &lines {
+            let _ = self.term.write_line(&truncate_str(line, width, "…"));
+        }
+        state.drawn_lines = lines.len();
+    }
+
+    // 最終サマリーを作成
+    pub fn summary(&self, stats: &PipelineStats) -> Summary {
+        let state = self.state();
+        let duration = self.started.elapsed();
+
+        Summary {
+            files: state.total_files,
+            files_completed: state.files_done,
+            chunks: state.chunks,
+            points: state.upserted,
+            duplicates: state.duplicates,
+            skipped: state.skipped,
+            failed: state.failed,
+            dead_lettered: state.dead_lettered,
+            undecodable: state.undecodable.clone(),
+            duration_secs: duration.as_secs_f64(),
+            stages: stats
+                .stages()
+                .iter()
+                .map(|stage| StageSummary {
+                    name: stage.name(),
+                    unit: stage.unit(),
+                    items: stage.items(),
+                    per_sec: stage.items() as f64 / duration.as_secs_f64().max(f64::EPSILON),
+                    busy_secs: stage.busy().as_secs_f64(),
+                })
+                .collect(),
+        }
+    }
+
+    // ファイルごとの結果（処理中のファイルはその時点までの所要時間）
+    pub fn file_reports(&self) -> Vec<FileReport> {
+        let state = self.state();
+        state
+            .files
+            .iter()
+            .zip(&state.started_at)
+            .map(|(report, started)| {
+                let mut report = report.clone();
+                if report.status == FileStatus::Incomplete {
+                    report.duration_secs = started.map(|started| started.elapsed().as_secs_f64());
+                }
+                report
+            })
+            .collect()
+    }
+}
+
+// 再描画タスクのハンドル
+pub struct Renderer {
+    stop_tx: watch::Sender<bool>,
+    handle: JoinHandle<()>,
+}
+
+impl Renderer {
+    pub async fn stop(self) {
+        let _ = self.stop_tx.send(true);
+        let _ = self.handle.await;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UndecodableFile {
+    pub file: String,
+    pub reason: String,
+}
+
+// ファイルの処理状況
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    // 未着手
+    #[default]
+    Pending,
+    // 処理中（実行が中断した場合はこのまま残る）
+    Incomplete,
+    Completed,
+    Skipped,
+    Failed,
+}
+
+// ファイルごとの結果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FileReport {
+    pub file: String,
+    pub status: FileStatus,
+    pub bytes: u64,
+    pub lines: u64,
+    // 前回の実行でコミット済みの行
+    pub resumed_lines: u64,
+    pub chunks: u64,
+    // 埋め込んだポイント数（upsertに失敗してデッドレターに記録した分を含む）
+    pub points: u64,
+    pub duplicates: u64,
+    pub dead_lettered: u64,
+    pub duration_secs: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped_reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StageSummary {
+    pub name: &'static str,
+    pub unit: &'static str,
+    pub items: u64,
+    pub per_sec: f64,
+    pub busy_secs: f64,
+}
+
+// インジェスト結果のサマリー
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    pub files: u64,
+    pub files_completed: u64,
+    pub chunks: u64,
+    pub points: u64,
+    pub duplicates: u64,
+    pub skipped: u64,
+    pub failed: u64,
+    pub dead_lettered: u64,
+    pub undecodable: Vec<UndecodableFile>,
+    pub duration_secs: f64,
+    pub stages: Vec<StageSummary>,
+}
+
+impl Summary {
+    pub fn print(&self, mode: OutputMode) {
+        if mode == OutputMode::Json {
+            match serde_json::to_string(self) {
+                Ok(json) => println!("{}", json),
+                Err(e) => eprintln!("Failed to serialize summary: {}", e),
+            }
+            return;
+        }
+
+        let rows = [
+            ("files", self.files.to_string()),
+            ("completed", self.files_completed.to_string()),
+            ("chunks", self.chunks.to_string()),
+            ("points", self.points.to_string()),
+            ("duplicates", self.duplicates.to_string()),
+            ("skipped", self.skipped.to_string()),
+            ("failed", self.failed.to_string()),
+            ("dead letters", self.dead_lettered.to_string()),
+            (
+                "duration",
+                format_duration(Duration::from_secs_f64(self.duration_secs)),
+            ),
+        ];
+
+        println!("=== Ingestion summary ===");
+        for (label, value) in rows {
+            println!("{:<12} {:>12}", label, value);
+        }
+
+        if !self.undecodable.is_empty() {
+            println!("--- Undecodable files ---");
+            for file in &self.undecodable {
+                println!("{}: {}", file.file, file.reason);
+            }
+        }
+
+        println!("--- Stage throughput ---");
+        for stage in &self.stages {
+            println!(
+                "{:<10} {:>12} {:<7} {:>10.1}/s  busy {:.1}s",
+                stage.name, stage.items, stage.unit, stage.per_sec, stage.busy_secs
+            );
+        }
+    }
+}
+
+fn bar(ratio: f64) -> String {
+    let filled = ((ratio * BAR_WIDTH as f64).round() as usize).min(BAR_WIDTH);
+    format!(
+        "[{}{}]",
+        style("=".repeat(filled)).green(),
+        " ".repeat(BAR_WIDTH - filled)
+    )
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+}
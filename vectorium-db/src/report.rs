@@ -0,0 +1,151 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use vectorium_common::EmbeddingModel;
+
+use crate::atomic;
+use crate::collections;
+use crate::progress::{FileReport, Summary};
+
+// 実行結果のレポート（CI向けのJSON）と、失敗として扱うしきい値
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ReportConfig {
+    // レポートの書き出し先（省略時は書き出さない、--report で上書き）
+    pub path: Option<PathBuf>,
+    // 以下を超えるとエイリアスを切り替えず、終了コードを非0にする（省略時は上限なし）
+    pub max_dead_letters: Option<u64>,
+    pub max_skipped_files: Option<u64>,
+    // チャンク数に対するデッドレターの割合（0.0〜1.0）
+    pub max_error_rate: Option<f64>,
+}
+
+impl ReportConfig {
+    // 超えたしきい値の説明
+    pub fn violations(&self, summary: &Summary) -> Vec<String> {
+        let mut violations = Vec::new();
+
+        if let Some(max) = self
+            .max_dead_letters
+            .filter(|&max| summary.dead_lettered > max)
+        {
+            violations.push(format!(
+                "dead letters {} > max_dead_letters {}",
+                summary.dead_lettered, max
+            ));
+        }
+        if let Some(max) = self.max_skipped_files.filter(|&max| summary.skipped > max) {
+            violations.push(format!(
+                "skipped files {} > max_skipped_files {}",
+                summary.skipped, max
+            ));
+        }
+        // デッドレターに記録したチャンクも作成済みのチャンクに含まれる
+        let rate = summary.dead_lettered as f64 / summary.chunks.max(1) as f64;
+        if let Some(max) = self.max_error_rate.filter(|&max| rate > max) {
+            violations.push(format!("error rate {:.4} > max_error_rate {}", rate, max));
+        }
+
+        violations
+    }
+}
+
+// 実行の結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Succeeded,
+    // しきい値を超えたためエイリアスを切り替えなかった
+    ThresholdExceeded,
+    Failed,
+}
+
+// 埋め込みに使ったモデル
+#[derive(Debug, Serialize)]
+pub struct ModelReport {
+    // ベクトル名
+    pub name: &'static str,
+    pub model: String,
+    pub dimension: u64,
+}
+
+impl From<EmbeddingModel> for ModelReport {
+    fn from(model: EmbeddingModel) -> Self {
+        Self {
+            name: model.name(),
+            model: format!("{:?}", model.model_type()),
+            dimension: model.dimension(),
+        }
+    }
+}
+
+// インジェストのレポート
+#[derive(Debug, Serialize)]
+pub struct IngestReport {
+    pub status: RunStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<String>,
+    pub finished_at: String,
+    pub alias: String,
+    // 投入先のコレクションとそのバージョン番号
+    pub collection: String,
+    pub version: Option<u32>,
+    // エイリアスを切り替えたか、切り替え前に公開していたコレクション
+    pub published: bool,
+    pub previous_collection: Option<String>,
+    // エイリアス導入前の実コレクションを移した（削除からエイリアス作成までは検索できなかった）
+    pub retired_legacy: bool,
+    pub deleted_collections: Vec<String>,
+    // 公開中のバージョンから引き継いだローダー（text/mail/chat/git）のポイント
+    pub carried_points: u64,
+    pub models: Vec<ModelReport>,
+    pub resumed: bool,
+    // 前回の実行で完了済みのファイル（files には含まない）
+    pub files_already_completed: u64,
+    pub totals: Summary,
+    pub files: Vec<FileReport>,
+}
+
+impl IngestReport {
+    // 投入先のコレクションとモデルは決まった時点で設定する（決まる前に失敗した場合は空のまま）
+    pub fn new(alias: &str) -> Self {
+        Self {
+            status: RunStatus::Succeeded,
+            error: None,
+            violations: Vec::new(),
+            finished_at: String::new(),
+            alias: alias.to_string(),
+            collection: String::new(),
+            version: None,
+            published: false,
+            previous_collection: None,
+            retired_legacy: false,
+            deleted_collections: Vec::new(),
+            carried_points: 0,
+            models: Vec::new(),
+            resumed: false,
+            files_already_completed: 0,
+            totals: Summary::default(),
+            files: Vec::new(),
+        }
+    }
+
+    pub fn set_collection(&mut self, collection_name: &str) {
+        self.collection = collection_name.to_string();
+        self.version = collections::parse_version(&self.alias, collection_name);
+    }
+
+    pub fn failed(&mut self, error: &anyhow::Error) {
+        self.status = RunStatus::Failed;
+        self.error = Some(format!("{:#}", error));
+    }
+
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        self.finished_at = chrono::Utc::now().to_rfc3339();
+        let content = serde_json::to_vec_pretty(self).context("Failed to serialize report")?;
+        atomic::write(path, &content, "report")
+    }
+}
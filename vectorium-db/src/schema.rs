@@ -0,0 +1,260 @@
+use anyhow::{Context, Result, bail};
+use qdrant_client::Qdrant;
+use qdrant_client::qdrant::value::Kind;
+use qdrant_client::qdrant::{
+    CreateFieldIndexCollectionBuilder, DatetimeIndexParamsBuilder, FieldType,
+    IntegerIndexParamsBuilder, KeywordIndexParamsBuilder, TextIndexParamsBuilder, TokenizerType,
+    Value,
+};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+// ペイロードフィールドの型（Qdrantのペイロードインデックス種別に対応）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadFieldType {
+    Keyword,
+    Integer,
+    Datetime,
+    Text,
+}
+
+// フィールド定義
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PayloadField {
+    #[serde(rename = "type")]
+    pub field_type: PayloadFieldType,
+    #[serde(default)]
+    pub required: bool,
+    // インデックスを作成しない場合は false
+    #[serde(default = "default_indexed")]
+    pub indexed: bool,
+    // テナントを分けるキー（keyword のみ、テナント単位でデータを配置して検索を速くする）
+    #[serde(default)]
+    pub is_tenant: bool,
+}
+
+fn default_indexed() -> bool {
+    true
+}
+
+impl PayloadField {
+    pub fn new(field_type: PayloadFieldType, required: bool) -> Self {
+        Self {
+            field_type,
+            required,
+            indexed: true,
+            is_tenant: false,
+        }
+    }
+}
+
+// ペイロードスキーマ（フィールド名 -> 定義）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "SchemaConfig")]
+pub struct PayloadSchema {
+    pub fields: BTreeMap<String, PayloadField>,
+}
+
+// 設定ファイルの [schema]（組み込みフィールドに追加するフィールド）
+#[derive(Deserialize)]
+struct SchemaConfig {
+    #[serde(default)]
+    fields: BTreeMap<String, PayloadField>,
+}
+
+// 組み込みフィールドはインジェスターが値を設定するため、同じ定義の再掲のみ許す
+impl TryFrom<SchemaConfig> for PayloadSchema {
+    type Error = String;
+
+    fn try_from(config: SchemaConfig) -> Result<Self, Self::Error> {
+        let mut schema = PayloadSchema::default();
+        for (name, field) in config.fields {
+            match schema.fields.get(&name) {
+                Some(builtin) if *builtin != field => {
+                    return Err(format!("built-in payload field {} cannot be changed", name));
+                }
+                _ => {
+                    schema.fields.insert(name, field);
+                }
+            }
+        }
+        Ok(schema)
+    }
+}
+
+impl Default for PayloadSchema {
+    fn default() -> Self {
+        let fields = [
+            (
+                "title".to_string(),
+                PayloadField::new(PayloadFieldType::Keyword, true),
+            ),
+            (
+                "text".to_string(),
+                PayloadField::new(PayloadFieldType::Text, true),
+            ),
+            (
+                "source".to_string(),
+                PayloadField::new(PayloadFieldType::Keyword, true),
+            ),
+            (
+                "tenant_id".to_string(),
+                PayloadField {
+                    is_tenant: true,
+                    ..PayloadField::new(PayloadFieldType::Keyword, true)
+                },
+            ),
+            // 移行が必要なポイントの検索に使う（migrate.rs）
+            (
+                "schema_version".to_string(),
+                PayloadField::new(PayloadFieldType::Integer, true),
+            ),
+            // メールをスレッド単位で絞り込む（mail.rs、メール以外のポイントにはない）
+            (
+                "thread_id".to_string(),
+                PayloadField::new(PayloadFieldType::Keyword, false),
+            ),
+            // 差分判定にのみ使うためインデックスは不要
+            (
+                "content_hash".to_string(),
+                PayloadField {
+                    indexed: false,
+                    ..PayloadField::new(PayloadFieldType::Keyword, true)
+                },
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        Self { fields }
+    }
+}
+
+impl PayloadSchema {
+    // スキーマに従ってペイロードインデックスを作成
+    pub async fn create_indexes(&self, client: &Qdrant, collection_name: &str) -> Result<()> {
+        for (name, field) in self.fields.iter().filter(|(_, field)| field.indexed) {
+            let builder = match field.field_type {
+                PayloadFieldType::Keyword => CreateFieldIndexCollectionBuilder::new(
+                    collection_name,
+                    name,
+                    FieldType::Keyword,
+                )
+                .field_index_params(
+                    KeywordIndexParamsBuilder::default().is_tenant(field.is_tenant),
+                ),
+                PayloadFieldType::Integer => CreateFieldIndexCollectionBuilder::new(
+                    collection_name,
+                    name,
+                    FieldType::Integer,
+                )
+                .field_index_params(IntegerIndexParamsBuilder::new(true, true)),
+                PayloadFieldType::Datetime => CreateFieldIndexCollectionBuilder::new(
+                    collection_name,
+                    name,
+                    FieldType::Datetime,
+                )
+                .field_index_params(DatetimeIndexParamsBuilder::default()),
+                // 日本語を含むため多言語トークナイザを使用
+                PayloadFieldType::Text => {
+                    CreateFieldIndexCollectionBuilder::new(collection_name, name, FieldType::Text)
+                        .field_index_params(TextIndexParamsBuilder::new(
+                            TokenizerType::Multilingual,
+                        ))
+                }
+            };
+
+            client
+                .create_field_index(builder.wait(true))
+                .await
+                .with_context(|| format!("Failed to create payload index: {}", name))?;
+        }
+
+        Ok(())
+    }
+
+    // ペイロードがスキーマに適合しているか検証
+    pub fn validate(&self, payload: &HashMap<String, Value>) -> Result<()> {
+        for (name, field) in &self.fields {
+            match payload.get(name).and_then(|value| value.kind.as_ref()) {
+                None | Some(Kind::NullValue(_)) => {
+                    if field.required {
+                        bail!("Missing required payload field: {}", name);
+                    }
+                }
+                Some(kind) => {
+                    if !field.field_type.accepts(kind) {
+                        bail!(
+                            "Payload field '{}' does not match schema type {:?}",
+                            name,
+                            field.field_type
+                        );
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl PayloadFieldType {
+    // 値の種類がフィールド型に適合するか（配列は全要素を検査）
+    fn accepts(&self, kind: &Kind) -> bool {
+        match (self, kind) {
+            (_, Kind::ListValue(list)) => list
+                .values
+                .iter()
+                .filter_map(|value| value.kind.as_ref())
+                .all(|kind| !matches!(kind, Kind::ListValue(_)) && self.accepts(kind)),
+            (PayloadFieldType::Keyword | PayloadFieldType::Text, Kind::StringValue(_)) => true,
+            (PayloadFieldType::Integer, Kind::IntegerValue(_)) => true,
+            (PayloadFieldType::Datetime, Kind::StringValue(s)) => is_datetime(s),
+            _ => false,
+        }
+    }
+}
+
+// Qdrantが受け付ける日時表現（RFC 3339、タイムゾーンなし日時、日付のみ）
+fn is_datetime(value: &str) -> bool {
+    chrono::DateTime::parse_from_rfc3339(value).is_ok()
+        || chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f").is_ok()
+        || chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f").is_ok()
+        || chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(toml: &str) -> std::result::Result<PayloadSchema, toml::de::Error> {
+        toml::from_str(toml)
+    }
+
+    #[test]
+    fn user_fields_are_added_to_the_built_ins() {
+        let schema = parse(
+            r#"
+            [fields.category]
+            type = "keyword"
+            "#,
+        )
+        .unwrap();
+
+        let builtins = PayloadSchema::default();
+        assert_eq!(schema.fields.len(), builtins.fields.len() + 1);
+        for (name, field) in &builtins.fields {
+            assert_eq!(schema.fields.get(name), Some(field));
+        }
+        assert!(parse("").unwrap().fields.contains_key("tenant_id"));
+    }
+
+    #[test]
+    fn built_in_fields_cannot_be_changed() {
+        // 同じ定義の再掲は許す
+        assert!(parse("[fields.title]\ntype = \"keyword\"\nrequired = true").is_ok());
+        assert!(parse("[fields.title]\ntype = \"text\"\nrequired = true").is_err());
+        assert!(parse("[fields.source]\ntype = \"keyword\"").is_err());
+    }
+}
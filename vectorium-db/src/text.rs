@@ -0,0 +1,447 @@
+use anyhow::{Context, Result, bail};
+use qdrant_client::qdrant::{
+    Condition, DeletePointsBuilder, Filter, PointId, PointStruct, PointsIdsList,
+    ScrollPointsBuilder, Value,
+};
+use qdrant_client::{Payload, Qdrant};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use vectorium_common::{EmbeddingModel, get_qdrant_client};
+
+use crate::collections;
+use crate::config::{Config, DEFAULT_CONFIG_PATH};
+use crate::dedup::{Deduplicator, Location};
+use crate::pipeline::{self, Line, MergedRecord, Neighbors};
+use crate::vectors::{Embedders, start_embedders};
+
+// 既存ポイントを取得するscrollの1ページあたりの件数
+const SCROLL_PAGE_SIZE: u32 = 1000;
+
+// インジェスターが設定するため、メタデータでは上書きできないフィールド
+const RESERVED_FIELDS: [&str; 15] = [
+    "title",
+    "text",
+    "source",
+    "tenant_id",
+    "content_hash",
+    "parent_id",
+    "parent_text",
+    "seq",
+    "prev_seq",
+    "prev_id",
+    "next_seq",
+    "next_id",
+    "schema_version",
+    "merged_sources",
+    "duplicate_count",
+];
+
+// 1ポイントになるテキスト（seq はソース内で安定した番号、ポイントIDの元になる）
+#[derive(Debug, Clone)]
+pub struct Passage {
+    pub seq: u64,
+    pub text: String,
+    pub metadata: HashMap<String, Value>,
+}
+
+// テキスト投入の結果
+#[derive(Debug, Serialize)]
+pub struct IngestedText {
+    pub collection: String,
+    pub source: String,
+    pub lines: u64,
+    pub duplicates: u64,
+    pub points: u64,
+}
+
+// ファイルを介さずにテキストを公開中のコレクションへ投入する（モデルを読み込んだまま繰り返し使える）
+pub struct TextIngester {
+    client: Qdrant,
+    config: Config,
+    embedders: Embedders,
+    tenant: String,
+}
+
+// テキストのソース名（同じテナントが同じタイトルで再投入すると前回のポイントを置き換える）
+// ソース名はテナント間で重なり得るが、ポイントIDと削除の条件はテナントごとに分かれる
+pub fn text_source_name(title: &str) -> String {
+    format!("text:{}", title)
+}
+
+// ローダーが付けるソース名の接頭辞（ファイル由来のソースはファイルパス）
+const LOADER_SOURCE_PREFIXES: [&str; 4] = ["text:", "mail:", "chat:", "git:"];
+
+pub fn is_loader_source(source: &str) -> bool {
+    LOADER_SOURCE_PREFIXES
+        .iter()
+        .any(|prefix| source.starts_with(prefix))
+}
+
+// ローダーのポイントはファイルから作り直せないため、公開中のバージョンから新しいバージョンへ引き継ぐ
+pub async fn carry_over_loader_points(
+    client: &Qdrant,
+    config: &Config,
+    collection_name: &str,
+) -> Result<u64> {
+    match collections::resolve(client, &config.collection).await? {
+        Some(published) if published != collection_name => {
+            collections::carry_over(
+                client,
+                &published,
+                collection_name,
+                |source| source.is_some_and(is_loader_source),
+                &config.processing,
+            )
+            .await
+        }
+        _ => Ok(0),
+    }
+}
+
+impl TextIngester {
+    // 指定したモデルを読み込む（空の場合は [vectors] models の全て）
+    pub async fn new(client: Qdrant, config: Config, models: &[EmbeddingModel]) -> Result<Self> {
+        let models = if models.is_empty() {
+            config.vectors.models()
+        } else {
+            models.to_vec()
+        };
+        let embedders = start_embedders(&config, models).await?;
+        let tenant = config.default_tenant.clone();
+
+        Ok(Self {
+            client,
+            config,
+            embedders,
+            tenant,
+        })
+    }
+
+    // 投入先のテナント（省略時は default_tenant）
+    pub fn with_tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.tenant = tenant.into();
+        self
+    }
+
+    pub fn tenant(&self) -> &str {
+        &self.tenant
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    // 公開中のコレクション（ローダーは常にエイリアスの指す先へ書き込む）
+    pub async fn collection(&self) -> Result<String> {
+        let alias = &self.config.collection;
+        collections::resolve(&self.client, alias)
+            .await?
+            .with_context(|| format!("Collection {} does not exist; run ingest first", alias))
+    }
+
+    // テキストを行ごとに埋め込み、同じソースの既存ポイントを置き換える
+    // 次回のフルインジェストでは公開前に新しいバージョンへ引き継がれる
+    pub async fn ingest(&self, title: &str, text: &str, metadata: Payload) -> Result<IngestedText> {
+        self.ingest_document(&text_source_name(title), title, text, metadata)
+            .await
+    }
+
+    // ソース名を指定してテキストを投入する（ローダー向け）
+    pub async fn ingest_document(
+        &self,
+        source: &str,
+        title: &str,
+        text: &str,
+        metadata: Payload,
+    ) -> Result<IngestedText> {
+        let metadata = HashMap::<String, Value>::from(metadata);
+        let collection_name = self.collection().await?;
+
+        // ファイルと同じく空行を除き、テキスト内の重複行は最初の1行だけ残す
+        let mut dedup = Deduplicator::new(&self.config.dedup);
+        let mut passages = Vec::new();
+        let mut total = 0u64;
+        for text in text.lines().map(str::trim_end) {
+            let normalized = self.config.normalize.apply(text);
+            if normalized.is_empty() {
+                continue;
+            }
+            let seq = total;
+            total += 1;
+
+            if dedup
+                .check(Location { file: 0, seq }, &normalized)
+                .is_some()
+            {
+                continue;
+            }
+            passages.push(Passage {
+                seq,
+                text: text.to_string(),
+                metadata: metadata.clone(),
+            });
+        }
+
+        // 埋め込みと書き込みが終わってから、今回のテキストにない行のポイントを削除する
+        // （途中で失敗しても前回のポイントは検索できるまま残る）
+        let points = self.embed(source, title, &passages).await?;
+        let count = points.len() as u64;
+        let previous = self
+            .point_ids(
+                &collection_name,
+                vec![Condition::matches("source", source.to_string())],
+            )
+            .await?;
+        let current: HashSet<PointId> =
+            points.iter().filter_map(|point| point.id.clone()).collect();
+        self.upsert(&collection_name, points).await?;
+        let stale: Vec<PointId> = previous
+            .into_iter()
+            .filter(|id| !current.contains(id))
+            .collect();
+        self.delete_ids(&collection_name, stale).await?;
+
+        // ファイルと同じく、除外した重複行は残した行のポイントに記録する
+        let merged: Vec<MergedRecord> = dedup
+            .into_merged()
+            .into_iter()
+            .map(|(survivor, merged)| MergedRecord {
+                id: pipeline::point_id(&self.tenant, source, survivor.seq),
+                locations: merged
+                    .locations
+                    .iter()
+                    .map(|location| format!("{}#{}", source, location.seq))
+                    .collect(),
+                count: merged.count,
+            })
+            .collect();
+        pipeline::set_merged(&self.client, &collection_name, &merged).await?;
+
+        Ok(IngestedText {
+            collection: collection_name,
+            source: source.to_string(),
+            lines: total,
+            duplicates: total - passages.len() as u64,
+            points: count,
+        })
+    }
+
+    // パッセージを埋め込み、メタデータを加えたポイントを作る（渡された順に前後のパッセージとつなぐ）
+    pub async fn embed(
+        &self,
+        source: &str,
+        title: &str,
+        passages: &[Passage],
+    ) -> Result<Vec<PointStruct>> {
+        self.embed_passages(source, title, passages, true).await
+    }
+
+    // 互いに独立したパッセージ（コミットメッセージなど）を前後とつながずに埋め込む
+    pub async fn embed_unlinked(
+        &self,
+        source: &str,
+        title: &str,
+        passages: &[Passage],
+    ) -> Result<Vec<PointStruct>> {
+        self.embed_passages(source, title, passages, false).await
+    }
+
+    async fn embed_passages(
+        &self,
+        source: &str,
+        title: &str,
+        passages: &[Passage],
+        link: bool,
+    ) -> Result<Vec<PointStruct>> {
+        let chunk_size = self.config.processing.chunk_size.max(1);
+        let mut points: Vec<PointStruct> = Vec::with_capacity(passages.len());
+        let neighbors: Vec<Neighbors> = (0..passages.len())
+            .map(|i| {
+                if !link {
+                    return Neighbors::default();
+                }
+                Neighbors {
+                    prev: i.checked_sub(1).map(|prev| passages[prev].seq),
+                    next: passages.get(i + 1).map(|next| next.seq),
+                }
+            })
+            .collect();
+
+        for (passages, neighbors) in passages
+            .chunks(chunk_size)
+            .zip(neighbors.chunks(chunk_size))
+        {
+            if let Some(field) = passages
+                .iter()
+                .flat_map(|passage| passage.metadata.keys())
+                .find(|key| RESERVED_FIELDS.contains(&key.as_str()))
+            {
+                bail!("Metadata cannot set reserved field: {}", field);
+            }
+
+            let lines: Vec<Line> = passages
+                .iter()
+                .zip(neighbors)
+                .map(|(passage, &neighbors)| Line {
+                    text: passage.text.clone(),
+                    normalized: self.config.normalize.apply(&passage.text),
+                    context: None,
+                    section: None,
+                    neighbors,
+                })
+                .collect();
+            let seqs: Vec<u64> = passages.iter().map(|passage| passage.seq).collect();
+            let result = pipeline::process_chunk(
+                &self.embedders,
+                &lines,
+                &seqs,
+                source,
+                &self.tenant,
+                title,
+                &self.config.schema,
+            )
+            .await?;
+
+            // メタデータを加えたペイロードも検証してから書き込む
+            for (mut point, passage) in result.points.into_iter().zip(passages) {
+                point.payload.extend(passage.metadata.clone());
+                self.config
+                    .schema
+                    .validate(&point.payload)
+                    .with_context(|| format!("Invalid metadata for {}#{}", source, passage.seq))?;
+                points.push(point);
+            }
+        }
+
+        Ok(points)
+    }
+
+    pub async fn upsert(&self, collection_name: &str, points: Vec<PointStruct>) -> Result<()> {
+        pipeline::upsert_points(
+            &self.client,
+            collection_name,
+            points,
+            &self.config.processing,
+        )
+        .await
+    }
+
+    // 条件に投入先テナントを加えたフィルター
+    fn tenant_filter(&self, conditions: Vec<Condition>) -> Filter {
+        let mut filter = Filter::must(conditions);
+        filter
+            .must
+            .push(Condition::matches("tenant_id", self.tenant.clone()));
+        filter
+    }
+
+    // 条件に一致する投入先テナントのポイントを削除
+    pub async fn delete(&self, collection_name: &str, conditions: Vec<Condition>) -> Result<()> {
+        self.client
+            .delete_points(
+                DeletePointsBuilder::new(collection_name)
+                    .points(self.tenant_filter(conditions))
+                    .wait(true),
+            )
+            .await
+            .context("Failed to delete previous points")?;
+        Ok(())
+    }
+
+    // 条件に一致する投入先テナントのポイントのID
+    async fn point_ids(
+        &self,
+        collection_name: &str,
+        conditions: Vec<Condition>,
+    ) -> Result<Vec<PointId>> {
+        let filter = self.tenant_filter(conditions);
+        let mut ids = Vec::new();
+        let mut offset: Option<PointId> = None;
+
+        loop {
+            let mut request = ScrollPointsBuilder::new(collection_name)
+                .filter(filter.clone())
+                .limit(SCROLL_PAGE_SIZE)
+                .with_payload(false)
+                .with_vectors(false);
+            if let Some(offset) = offset.take() {
+                request = request.offset(offset);
+            }
+
+            let response = self
+                .client
+                .scroll(request)
+                .await
+                .context("Failed to scroll previous points")?;
+            ids.extend(response.result.into_iter().filter_map(|point| point.id));
+
+            match response.next_page_offset {
+                Some(next) => offset = Some(next),
+                None => break,
+            }
+        }
+
+        Ok(ids)
+    }
+
+    // IDを指定してポイントを削除
+    async fn delete_ids(&self, collection_name: &str, ids: Vec<PointId>) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        self.client
+            .delete_points(
+                DeletePointsBuilder::new(collection_name)
+                    .points(PointsIdsList { ids })
+                    .wait(true),
+            )
+            .await
+            .context("Failed to delete stale points")?;
+        Ok(())
+    }
+}
+
+// 既定の設定ファイルと接続先でテキストを1件投入する（毎回モデルを読み込むため、繰り返す場合は TextIngester を使う）
+pub async fn ingest_text(title: &str, text: &str, metadata: Payload) -> Result<IngestedText> {
+    let config = Config::load(Path::new(DEFAULT_CONFIG_PATH))?;
+    let ingester = TextIngester::new(get_qdrant_client(), config, &[]).await?;
+    ingester.ingest(title, text, metadata).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::point_id;
+
+    #[test]
+    fn loader_sources_do_not_collide_across_tenants() {
+        // 標準入力・メール・チャットのソース名はテナントを含まない
+        let sources = [
+            text_source_name("meeting notes"),
+            "mail:<abc@example.com>".to_string(),
+            "chat:general".to_string(),
+        ];
+        for source in &sources {
+            for seq in [0, 1, 1_700_000_000_000] {
+                assert_ne!(
+                    point_id("acme", source, seq),
+                    point_id("globex", source, seq)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn only_loader_sources_are_carried_over() {
+        assert!(is_loader_source(&text_source_name("meeting notes")));
+        assert!(is_loader_source("mail:<abc@example.com>"));
+        assert!(is_loader_source("chat:general"));
+        assert!(is_loader_source("git:vectorium"));
+        // ファイル由来のソースは次のインジェストで作り直す
+        assert!(!is_loader_source("/data/docs/notes.txt"));
+        assert!(!is_loader_source("docs/text.md"));
+    }
+}
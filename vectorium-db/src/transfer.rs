@@ -0,0 +1,784 @@
+use anyhow::{Context, Result, bail};
+use arrow_array::builder::{FixedSizeListBuilder, Float32Builder};
+use arrow_array::cast::AsArray;
+use arrow_array::types::Float32Type;
+use arrow_array::{Array, ArrayRef, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use clap::ValueEnum;
+use parquet::arrow::ArrowWriter;
+use parquet::arrow::arrow_reader::{ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder};
+use parquet::file::properties::WriterProperties;
+use parquet::format::KeyValue;
+use qdrant_client::qdrant::point_id::PointIdOptions;
+use qdrant_client::qdrant::vector_output::Vector;
+use qdrant_client::qdrant::vectors_output::VectorsOptions;
+use qdrant_client::qdrant::{
+    CountPointsBuilder, PointId, PointStruct, RetrievedPoint, ScrollPointsBuilder, VectorOutput,
+    Vectors, vectors_config,
+};
+use qdrant_client::{Payload, Qdrant};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use vectorium_common::EmbeddingModel;
+
+use crate::collections::{self, Published, VECTOR_DIMENSION};
+use crate::config::Config;
+use crate::pipeline;
+use crate::vectors::VectorsConfig;
+
+// エクスポートファイルの識別子と形式バージョン（v2 で名前付きベクトルに対応）
+const EXPORT_FORMAT: &str = "vectorium-export";
+const EXPORT_VERSION: u32 = 2;
+// Parquetのファイルメタデータにヘッダーを格納するキー
+const PARQUET_HEADER_KEY: &str = "vectorium.header";
+// scrollの1ページあたりの件数
+const EXPORT_PAGE_SIZE: u32 = 256;
+
+// ファイル形式（省略時は拡張子から判定）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Jsonl,
+    Parquet,
+}
+
+impl Format {
+    fn resolve(format: Option<Format>, path: &Path) -> Format {
+        format.unwrap_or_else(|| match path.extension().and_then(|ext| ext.to_str()) {
+            Some("parquet") => Format::Parquet,
+            _ => Format::Jsonl,
+        })
+    }
+}
+
+// エクスポートしたベクトルのモデルと次元数（無名ベクトルの name は空文字列）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedVector {
+    pub name: String,
+    pub model: String,
+    pub dimension: u64,
+}
+
+impl ExportedVector {
+    fn new(name: &str, model: EmbeddingModel, dimension: u64) -> Self {
+        Self {
+            name: name.to_string(),
+            model: format!("{:?}", model.model_type()),
+            dimension,
+        }
+    }
+
+    // 設定のベクトル構成
+    fn configured(vectors: &VectorsConfig) -> Vec<Self> {
+        vectors
+            .models()
+            .into_iter()
+            .map(|model| {
+                if vectors.is_named() {
+                    Self::new(model.name(), model, model.dimension())
+                } else {
+                    Self::new("", model, VECTOR_DIMENSION)
+                }
+            })
+            .collect()
+    }
+
+    // コレクションのベクトル構成（名前付きベクトルの名前はモデル名、無名ベクトルは既定のモデル）
+    fn from_collection(
+        collection_name: &str,
+        vectors: Option<vectors_config::Config>,
+    ) -> Result<Vec<Self>> {
+        match vectors {
+            Some(vectors_config::Config::Params(params)) => {
+                Ok(vec![Self::new("", EmbeddingModel::default(), params.size)])
+            }
+            Some(vectors_config::Config::ParamsMap(params)) => params
+                .map
+                .into_iter()
+                .collect::<BTreeMap<_, _>>()
+                .into_iter()
+                .map(|(name, params)| {
+                    let model = EmbeddingModel::from_name(&name).with_context(|| {
+                        format!("Collection {} has unknown vector {}", collection_name, name)
+                    })?;
+                    Ok(Self::new(&name, model, params.size))
+                })
+                .collect(),
+            None => bail!("Collection {} has no vectors", collection_name),
+        }
+    }
+
+    // エラーメッセージ用の名前
+    fn label(&self) -> &str {
+        if self.name.is_empty() {
+            "(unnamed)"
+        } else {
+            &self.name
+        }
+    }
+
+    // Parquetの列名（無名ベクトルは v1 と同じ vector）
+    fn column(&self) -> String {
+        if self.name.is_empty() {
+            "vector".to_string()
+        } else {
+            format!("vector:{}", self.name)
+        }
+    }
+}
+
+// ファイル先頭（JSONLは1行目、Parquetはメタデータ）に書くヘッダー
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportHeader {
+    pub format: String,
+    pub version: u32,
+    pub collection: String,
+    #[serde(default)]
+    pub vectors: Vec<ExportedVector>,
+    // v1 の無名ベクトルのモデルと次元数（読み込み時に vectors へ移す）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimension: Option<u64>,
+    pub points: u64,
+    pub exported_at: String,
+}
+
+impl ExportHeader {
+    fn upgrade(mut self) -> Self {
+        let legacy = self.model.take().zip(self.dimension.take());
+        if let Some((model, dimension)) = legacy.filter(|_| self.vectors.is_empty()) {
+            self.vectors.push(ExportedVector {
+                name: String::new(),
+                model,
+                dimension,
+            });
+        }
+        self
+    }
+
+    // 取り込み先の設定とベクトル構成（名前・モデル・次元数）が一致するか
+    fn check(&self, expected: &[ExportedVector]) -> Result<()> {
+        for vector in expected {
+            let Some(exported) = self.vectors.iter().find(|v| v.name == vector.name) else {
+                bail!("Export has no vector {}", vector.label());
+            };
+            if exported.model != vector.model {
+                bail!(
+                    "Vector {} was embedded with {} but the config uses {}",
+                    vector.label(),
+                    exported.model,
+                    vector.model
+                );
+            }
+            if exported.dimension != vector.dimension {
+                bail!(
+                    "Vector {} has dimension {} in the export but {} in the config",
+                    vector.label(),
+                    exported.dimension,
+                    vector.dimension
+                );
+            }
+        }
+        if let Some(extra) = self
+            .vectors
+            .iter()
+            .find(|exported| !expected.iter().any(|v| v.name == exported.name))
+        {
+            bail!(
+                "Export has vector {} which is not in [vectors] models",
+                extra.label()
+            );
+        }
+        Ok(())
+    }
+}
+
+// エクスポートするポイント（数値IDは10進文字列で表す）
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportedPoint {
+    id: String,
+    // ベクトル名 -> ベクトル（無名ベクトルは空文字列）
+    #[serde(default)]
+    vectors: BTreeMap<String, Vec<f32>>,
+    // v1 の無名ベクトル（読み込み時に vectors へ移す）
+    #[serde(default, skip_serializing)]
+    vector: Option<Vec<f32>>,
+    payload: serde_json::Map<String, serde_json::Value>,
+}
+
+fn dense_vector(id: &str, vector: VectorOutput) -> Result<Vec<f32>> {
+    match vector.into_vector() {
+        Vector::Dense(dense) => Ok(dense.data),
+        _ => bail!("Point {} has an unsupported vector type", id),
+    }
+}
+
+impl ExportedPoint {
+    fn from_retrieved(point: RetrievedPoint) -> Result<Self> {
+        let id = match point.id.and_then(|id| id.point_id_options) {
+            Some(PointIdOptions::Uuid(uuid)) => uuid,
+            Some(PointIdOptions::Num(num)) => num.to_string(),
+            None => bail!("Point without id"),
+        };
+        let vectors = match point.vectors.and_then(|vectors| vectors.vectors_options) {
+            Some(VectorsOptions::Vector(vector)) => {
+                BTreeMap::from([(String::new(), dense_vector(&id, vector)?)])
+            }
+            Some(VectorsOptions::Vectors(named)) => named
+                .vectors
+                .into_iter()
+                .map(|(name, vector)| Ok((name, dense_vector(&id, vector)?)))
+                .collect::<Result<_>>()?,
+            None => bail!("Point {} has no vector", id),
+        };
+        let payload = Payload::from(point.payload).into();
+
+        Ok(Self {
+            id,
+            vectors,
+            vector: None,
+            payload,
+        })
+    }
+
+    fn upgrade(mut self) -> Self {
+        if let Some(vector) = self.vector.take() {
+            self.vectors.insert(String::new(), vector);
+        }
+        self
+    }
+
+    // ベクトルがヘッダーの構成どおりか（--models で一部のモデルだけ投入したポイントは一部のベクトルのみを持つ）
+    fn check(&self, expected: &[ExportedVector]) -> Result<()> {
+        if self.vectors.is_empty() {
+            bail!("Point {} has no vectors", self.id);
+        }
+        for (name, values) in &self.vectors {
+            let Some(vector) = expected.iter().find(|vector| &vector.name == name) else {
+                bail!(
+                    "Point {} has vector {} which is not in the export",
+                    self.id,
+                    name
+                );
+            };
+            if values.len() as u64 != vector.dimension {
+                bail!(
+                    "Point {} has dimension {} for vector {} (expected {})",
+                    self.id,
+                    values.len(),
+                    vector.label(),
+                    vector.dimension
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn into_point(self) -> PointStruct {
+        let id: PointId = match self.id.parse::<u64>() {
+            Ok(num) => num.into(),
+            Err(_) => self.id.into(),
+        };
+        let mut vectors = self.vectors;
+        let vectors: Vectors = match vectors.remove("") {
+            Some(vector) => vector.into(),
+            None => vectors.into_iter().collect::<HashMap<_, _>>().into(),
+        };
+        PointStruct::new(id, vectors, Payload::from(self.payload))
+    }
+}
+
+// 形式ごとの書き出し先
+enum PointWriter {
+    Jsonl(BufWriter<File>),
+    Parquet(Box<ArrowWriter<File>>, Vec<ExportedVector>),
+}
+
+impl PointWriter {
+    fn create(path: &Path, format: Format, header: &ExportHeader) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create export file: {}", path.display()))?;
+
+        match format {
+            Format::Jsonl => {
+                let mut writer = BufWriter::new(file);
+                serde_json::to_writer(&mut writer, header)?;
+                writer.write_all(b"\n")?;
+                Ok(PointWriter::Jsonl(writer))
+            }
+            Format::Parquet => {
+                let properties = WriterProperties::builder()
+                    .set_key_value_metadata(Some(vec![KeyValue::new(
+                        PARQUET_HEADER_KEY.to_string(),
+                        serde_json::to_string(header)?,
+                    )]))
+                    .build();
+                let writer =
+                    ArrowWriter::try_new(file, parquet_schema(&header.vectors), Some(properties))
+                        .context("Failed to create Parquet writer")?;
+                Ok(PointWriter::Parquet(
+                    Box::new(writer),
+                    header.vectors.clone(),
+                ))
+            }
+        }
+    }
+
+    fn write(&mut self, points: &[ExportedPoint]) -> Result<()> {
+        match self {
+            PointWriter::Jsonl(writer) => {
+                for point in points {
+                    serde_json::to_writer(&mut *writer, point)?;
+                    writer.write_all(b"\n")?;
+                }
+            }
+            PointWriter::Parquet(writer, vectors) => {
+                writer
+                    .write(&to_record_batch(points, vectors)?)
+                    .context("Failed to write Parquet row group")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            PointWriter::Jsonl(mut writer) => writer.flush()?,
+            PointWriter::Parquet(writer, _) => {
+                writer.close().context("Failed to finish Parquet file")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn vector_field(dimension: u64) -> DataType {
+    DataType::FixedSizeList(
+        Arc::new(Field::new("item", DataType::Float32, false)),
+        dimension as i32,
+    )
+}
+
+// ベクトルごとに1列（列名は ExportedVector::column、そのベクトルを持たないポイントは null）
+fn parquet_schema(vectors: &[ExportedVector]) -> SchemaRef {
+    let mut fields = vec![Field::new("id", DataType::Utf8, false)];
+    fields.extend(
+        vectors
+            .iter()
+            .map(|vector| Field::new(vector.column(), vector_field(vector.dimension), true)),
+    );
+    // ペイロードはJSON文字列で保持
+    fields.push(Field::new("payload", DataType::Utf8, false));
+    Arc::new(Schema::new(fields))
+}
+
+fn to_record_batch(points: &[ExportedPoint], vectors: &[ExportedVector]) -> Result<RecordBatch> {
+    let ids = StringArray::from_iter_values(points.iter().map(|point| point.id.as_str()));
+    let mut columns: Vec<ArrayRef> = vec![Arc::new(ids)];
+
+    for vector in vectors {
+        let dimension = vector.dimension as usize;
+        let mut list = FixedSizeListBuilder::with_capacity(
+            Float32Builder::with_capacity(points.len() * dimension),
+            vector.dimension as i32,
+            points.len(),
+        )
+        .with_field(Arc::new(Field::new("item", DataType::Float32, false)));
+        for point in points {
+            match point.vectors.get(&vector.name) {
+                Some(values) if values.len() == dimension => {
+                    list.values().append_slice(values);
+                    list.append(true);
+                }
+                Some(values) => bail!(
+                    "Point {} has dimension {} for vector {} (expected {})",
+                    point.id,
+                    values.len(),
+                    vector.label(),
+                    vector.dimension
+                ),
+                // null の行も次元数分の値を埋める
+                None => {
+                    list.values().append_slice(&vec![0.0; dimension]);
+                    list.append(false);
+                }
+            }
+        }
+        columns.push(Arc::new(list.finish()));
+    }
+
+    let payloads = points
+        .iter()
+        .map(|point| serde_json::to_string(&point.payload))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    columns.push(Arc::new(StringArray::from(payloads)));
+
+    RecordBatch::try_new(parquet_schema(vectors), columns).context("Failed to build record batch")
+}
+
+// 形式ごとの読み込み元
+enum PointReader {
+    Jsonl(Lines<BufReader<File>>),
+    Parquet(ParquetRecordBatchReader, Vec<ExportedVector>),
+}
+
+impl PointReader {
+    fn open(path: &Path, format: Format, batch_size: usize) -> Result<(ExportHeader, Self)> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open import file: {}", path.display()))?;
+
+        match format {
+            Format::Jsonl => {
+                let mut lines = BufReader::new(file).lines();
+                let header = lines.next().context("Import file is empty")??;
+                let header: ExportHeader =
+                    serde_json::from_str(&header).context("Failed to parse export header")?;
+                Ok((header.upgrade(), PointReader::Jsonl(lines)))
+            }
+            Format::Parquet => {
+                let builder = ParquetRecordBatchReaderBuilder::try_new(file)
+                    .context("Failed to open Parquet file")?;
+                let header = builder
+                    .metadata()
+                    .file_metadata()
+                    .key_value_metadata()
+                    .and_then(|metadata| {
+                        metadata
+                            .iter()
+                            .find(|entry| entry.key == PARQUET_HEADER_KEY)
+                    })
+                    .and_then(|entry| entry.value.as_deref())
+                    .context("Parquet file has no export header")?;
+                let header: ExportHeader =
+                    serde_json::from_str(header).context("Failed to parse export header")?;
+                let reader = builder
+                    .with_batch_size(batch_size)
+                    .build()
+                    .context("Failed to read Parquet file")?;
+                let header = header.upgrade();
+                let vectors = header.vectors.clone();
+                Ok((header, PointReader::Parquet(reader, vectors)))
+            }
+        }
+    }
+
+    // 最大 batch_size 件を読み込む（終端では空）
+    fn next_batch(&mut self, batch_size: usize) -> Result<Vec<ExportedPoint>> {
+        match self {
+            PointReader::Jsonl(lines) => lines
+                .by_ref()
+                .take(batch_size)
+                .map(|line| {
+                    let point: ExportedPoint =
+                        serde_json::from_str(&line?).context("Failed to parse exported point")?;
+                    Ok(point.upgrade())
+                })
+                .collect(),
+            PointReader::Parquet(reader, vectors) => match reader.next() {
+                Some(batch) => {
+                    from_record_batch(&batch.context("Failed to read row group")?, vectors)
+                }
+                None => Ok(Vec::new()),
+            },
+        }
+    }
+}
+
+fn from_record_batch(
+    batch: &RecordBatch,
+    vectors: &[ExportedVector],
+) -> Result<Vec<ExportedPoint>> {
+    let column = |name: &str| {
+        batch
+            .column_by_name(name)
+            .with_context(|| format!("Parquet file has no column {}", name))
+    };
+    let ids = column("id")?.as_string::<i32>();
+    let payloads = column("payload")?.as_string::<i32>();
+    let lists = vectors
+        .iter()
+        .map(|vector| {
+            Ok((
+                vector.name.as_str(),
+                column(&vector.column())?.as_fixed_size_list(),
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    (0..batch.num_rows())
+        .map(|row| {
+            Ok(ExportedPoint {
+                id: ids.value(row).to_string(),
+                vectors: lists
+                    .iter()
+                    .filter(|(_, list)| list.is_valid(row))
+                    .map(|(name, list)| {
+                        let values = list.value(row);
+                        (
+                            name.to_string(),
+                            values.as_primitive::<Float32Type>().values().to_vec(),
+                        )
+                    })
+                    .collect(),
+                vector: None,
+                payload: serde_json::from_str(payloads.value(row))
+                    .context("Failed to parse exported payload")?,
+            })
+        })
+        .collect()
+}
+
+// エイリアスが指すコレクションの全ポイントをファイルへ書き出す
+pub async fn export(
+    client: &Qdrant,
+    config: &Config,
+    path: &Path,
+    format: Option<Format>,
+) -> Result<u64> {
+    let collection_name = collections::resolve(client, &config.collection)
+        .await?
+        .with_context(|| format!("Collection {} does not exist", config.collection))?;
+    let points = client
+        .count(CountPointsBuilder::new(&collection_name).exact(true))
+        .await
+        .context("Failed to count points")?
+        .result
+        .map_or(0, |result| result.count);
+
+    // ヘッダーは設定ではなく書き出すコレクションのベクトル構成を表す
+    let vectors = client
+        .collection_info(collection_name.as_str())
+        .await
+        .with_context(|| format!("Failed to get collection info: {}", collection_name))?
+        .result
+        .and_then(|info| info.config)
+        .and_then(|config| config.params)
+        .and_then(|params| params.vectors_config)
+        .and_then(|vectors| vectors.config);
+    let header = ExportHeader {
+        format: EXPORT_FORMAT.to_string(),
+        version: EXPORT_VERSION,
+        collection: collection_name.clone(),
+        vectors: ExportedVector::from_collection(&collection_name, vectors)?,
+        model: None,
+        dimension: None,
+        points,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+    };
+    let mut writer = PointWriter::create(path, Format::resolve(format, path), &header)?;
+
+    let mut exported = 0u64;
+    let mut offset: Option<PointId> = None;
+    loop {
+        let mut request = ScrollPointsBuilder::new(&collection_name)
+            .limit(EXPORT_PAGE_SIZE)
+            .with_payload(true)
+            .with_vectors(true);
+        if let Some(offset) = offset.take() {
+            request = request.offset(offset);
+        }
+
+        let response = client
+            .scroll(request)
+            .await
+            .context("Failed to scroll points")?;
+        let page = response
+            .result
+            .into_iter()
+            .map(ExportedPoint::from_retrieved)
+            .collect::<Result<Vec<_>>>()?;
+        if !page.is_empty() {
+            writer.write(&page)?;
+            exported += page.len() as u64;
+        }
+
+        match response.next_page_offset {
+            Some(next) => offset = Some(next),
+            None => break,
+        }
+    }
+
+    writer.finish()?;
+    Ok(exported)
+}
+
+// 取り込み結果
+pub struct Imported {
+    // 取り込んだ新しいバージョンのコレクション
+    pub collection: String,
+    pub points: u64,
+    // エイリアスの切り替え前のコレクションと削除した古いバージョン
+    pub published: Published,
+}
+
+// ファイルから新しいバージョンのコレクションへ取り込み、検証後にエイリアスを切り替える
+pub async fn import(
+    client: &Qdrant,
+    config: &Config,
+    path: &Path,
+    format: Option<Format>,
+    batch_size: usize,
+) -> Result<Imported> {
+    let batch_size = batch_size.max(1);
+    let (header, mut reader) = PointReader::open(path, Format::resolve(format, path), batch_size)?;
+
+    if header.format != EXPORT_FORMAT || header.version > EXPORT_VERSION {
+        bail!(
+            "Unsupported export format: {} v{}",
+            header.format,
+            header.version
+        );
+    }
+    let expected = ExportedVector::configured(&config.vectors);
+    header.check(&expected)?;
+
+    let alias = config.collection.as_str();
+    let collection_name = collections::create_next_version(
+        client,
+        alias,
+        &config.schema,
+        &config.vectors,
+        &config.tuning,
+    )
+    .await?;
+
+    let mut imported = 0u64;
+    loop {
+        let batch = reader.next_batch(batch_size)?;
+        if batch.is_empty() {
+            break;
+        }
+
+        let points = batch
+            .into_iter()
+            .map(|point| {
+                point.check(&expected)?;
+                Ok(point.into_point())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let count = points.len() as u64;
+
+        pipeline::upsert_points(client, &collection_name, points, &config.processing)
+            .await
+            .context("Failed to upsert imported points")?;
+        imported += count;
+    }
+
+    let published = collections::publish(
+        client,
+        alias,
+        &collection_name,
+        imported,
+        false,
+        config.keep_versions,
+        &config.processing,
+    )
+    .await?;
+
+    Ok(Imported {
+        collection: collection_name,
+        points: imported,
+        published,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v1_exports_are_read_as_the_unnamed_vector() {
+        let header: ExportHeader = serde_json::from_str(
+            r#"{"format":"vectorium-export","version":1,"collection":"knowledge_v3",
+                "model":"DistiluseBaseMultilingualCased","dimension":512,
+                "points":1,"exported_at":"2024-01-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+        let header = header.upgrade();
+        header
+            .check(&ExportedVector::configured(&VectorsConfig::default()))
+            .unwrap();
+
+        let point: ExportedPoint =
+            serde_json::from_str(r#"{"id":"1","vector":[0.5,0.25],"payload":{}}"#).unwrap();
+        let point = point.upgrade();
+        assert_eq!(point.vectors.get(""), Some(&vec![0.5, 0.25]));
+    }
+
+    #[test]
+    fn named_vectors_must_match_the_config() {
+        let config = VectorsConfig {
+            models: vec![
+                EmbeddingModel::AllMiniLmL6V2,
+                EmbeddingModel::AllMiniLmL12V2,
+            ],
+        };
+        let expected = ExportedVector::configured(&config);
+        let header = |vectors: Vec<ExportedVector>| ExportHeader {
+            format: EXPORT_FORMAT.to_string(),
+            version: EXPORT_VERSION,
+            collection: "knowledge_v1".to_string(),
+            vectors,
+            model: None,
+            dimension: None,
+            points: 0,
+            exported_at: String::new(),
+        };
+
+        assert!(header(expected.clone()).check(&expected).is_ok());
+        // モデルが足りない、または取り込み先にないベクトルを含む
+        assert!(header(expected[..1].to_vec()).check(&expected).is_err());
+        assert!(header(expected.clone()).check(&expected[..1]).is_err());
+        // 同じ名前でも次元数が異なる
+        let mut resized = expected.clone();
+        resized[0].dimension = 512;
+        assert!(header(resized).check(&expected).is_err());
+    }
+
+    #[test]
+    fn points_may_lack_vectors_of_models_they_were_not_embedded_with() {
+        let config = VectorsConfig {
+            models: vec![
+                EmbeddingModel::AllMiniLmL6V2,
+                EmbeddingModel::AllMiniLmL12V2,
+            ],
+        };
+        let vectors = ExportedVector::configured(&config);
+        let point = |id: &str, names: &[&ExportedVector]| ExportedPoint {
+            id: id.to_string(),
+            vectors: names
+                .iter()
+                .map(|vector| (vector.name.clone(), vec![0.5; vector.dimension as usize]))
+                .collect(),
+            vector: None,
+            payload: serde_json::Map::new(),
+        };
+        let points = vec![
+            point("1", &[&vectors[0], &vectors[1]]),
+            point("2", &[&vectors[0]]),
+        ];
+
+        // Parquet では持たないベクトルの列が null になり、読み込むと含まれない
+        let batch = to_record_batch(&points, &vectors).unwrap();
+        let read = from_record_batch(&batch, &vectors).unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].vectors, points[0].vectors);
+        assert_eq!(read[1].vectors, points[1].vectors);
+        for point in &read {
+            point.check(&vectors).unwrap();
+        }
+
+        // ベクトルを1つも持たないポイントや、次元数の異なるベクトルは取り込まない
+        assert!(point("3", &[]).check(&vectors).is_err());
+        let mut resized = point("4", &[&vectors[0]]);
+        resized
+            .vectors
+            .insert(vectors[1].name.clone(), vec![0.5; 3]);
+        assert!(resized.check(&vectors).is_err());
+    }
+}
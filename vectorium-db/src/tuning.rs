@@ -0,0 +1,264 @@
+use anyhow::{Context, Result};
+use qdrant_client::Qdrant;
+use qdrant_client::qdrant::{
+    BinaryQuantizationBuilder, CollectionParamsDiffBuilder, CompressionRatio,
+    CreateCollectionBuilder, Disabled, HnswConfigDiff, HnswConfigDiffBuilder, OptimizersConfigDiff,
+    OptimizersConfigDiffBuilder, ProductQuantizationBuilder, ScalarQuantizationBuilder,
+    UpdateCollectionBuilder, VectorParamsDiffBuilder, VectorParamsDiffMap, quantization_config,
+    quantization_config_diff, vectors_config_diff,
+};
+use serde::Deserialize;
+
+use crate::vectors::VectorsConfig;
+
+// コレクションの性能チューニング（未指定の項目はQdrantの既定値のまま）
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TuningConfig {
+    pub hnsw: HnswTuning,
+    // 省略時は量子化しない（更新時は現在の設定を維持）
+    pub quantization: Option<Quantization>,
+    // ベクトルとペイロードをディスクに置く（メモリ使用量を抑える代わりに検索が遅くなる）
+    pub on_disk_vectors: Option<bool>,
+    pub on_disk_payload: Option<bool>,
+    pub optimizers: OptimizerTuning,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HnswTuning {
+    // ノードあたりのリンク数（大きいほど精度が上がりメモリを使う）
+    pub m: Option<u64>,
+    // 構築時の探索幅（大きいほど精度が上がり構築が遅くなる）
+    pub ef_construct: Option<u64>,
+    // この量（KB）未満のセグメントは全件走査する
+    pub full_scan_threshold: Option<u64>,
+    pub on_disk: Option<bool>,
+}
+
+// 量子化の方式
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Quantization {
+    // 量子化を無効化
+    Disabled,
+    Scalar {
+        // 外れ値を除く分位点（0.5〜1.0）
+        quantile: Option<f32>,
+        #[serde(default)]
+        always_ram: bool,
+    },
+    Product {
+        compression: Compression,
+        #[serde(default)]
+        always_ram: bool,
+    },
+    Binary {
+        #[serde(default)]
+        always_ram: bool,
+    },
+}
+
+// 直積量子化の圧縮率
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    X4,
+    X8,
+    X16,
+    X32,
+    X64,
+}
+
+impl Compression {
+    fn ratio(self) -> CompressionRatio {
+        match self {
+            Compression::X4 => CompressionRatio::X4,
+            Compression::X8 => CompressionRatio::X8,
+            Compression::X16 => CompressionRatio::X16,
+            Compression::X32 => CompressionRatio::X32,
+            Compression::X64 => CompressionRatio::X64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct OptimizerTuning {
+    // この量（KB）を超えたセグメントにインデックスを作成
+    pub indexing_threshold: Option<u64>,
+    // この量（KB）を超えたセグメントをメモリマップに移す
+    pub memmap_threshold: Option<u64>,
+    pub default_segment_number: Option<u64>,
+    pub max_segment_size: Option<u64>,
+    // 削除済みの割合がこれを超えたセグメントを再構築
+    pub deleted_threshold: Option<f64>,
+    pub flush_interval_sec: Option<u64>,
+}
+
+impl TuningConfig {
+    fn hnsw_config(&self) -> Option<HnswConfigDiff> {
+        let hnsw = &self.hnsw;
+        if hnsw.m.is_none()
+            && hnsw.ef_construct.is_none()
+            && hnsw.full_scan_threshold.is_none()
+            && hnsw.on_disk.is_none()
+        {
+            return None;
+        }
+
+        let mut builder = HnswConfigDiffBuilder::default();
+        if let Some(m) = hnsw.m {
+            builder = builder.m(m);
+        }
+        if let Some(ef_construct) = hnsw.ef_construct {
+            builder = builder.ef_construct(ef_construct);
+        }
+        if let Some(threshold) = hnsw.full_scan_threshold {
+            builder = builder.full_scan_threshold(threshold);
+        }
+        if let Some(on_disk) = hnsw.on_disk {
+            builder = builder.on_disk(on_disk);
+        }
+        Some(builder.build())
+    }
+
+    fn optimizers_config(&self) -> Option<OptimizersConfigDiff> {
+        let optimizers = &self.optimizers;
+        if optimizers.indexing_threshold.is_none()
+            && optimizers.memmap_threshold.is_none()
+            && optimizers.default_segment_number.is_none()
+            && optimizers.max_segment_size.is_none()
+            && optimizers.deleted_threshold.is_none()
+            && optimizers.flush_interval_sec.is_none()
+        {
+            return None;
+        }
+
+        let mut builder = OptimizersConfigDiffBuilder::default();
+        if let Some(threshold) = optimizers.indexing_threshold {
+            builder = builder.indexing_threshold(threshold);
+        }
+        if let Some(threshold) = optimizers.memmap_threshold {
+            builder = builder.memmap_threshold(threshold);
+        }
+        if let Some(number) = optimizers.default_segment_number {
+            builder = builder.default_segment_number(number);
+        }
+        if let Some(size) = optimizers.max_segment_size {
+            builder = builder.max_segment_size(size);
+        }
+        if let Some(threshold) = optimizers.deleted_threshold {
+            builder = builder.deleted_threshold(threshold);
+        }
+        if let Some(interval) = optimizers.flush_interval_sec {
+            builder = builder.flush_interval_sec(interval);
+        }
+        Some(builder.build())
+    }
+
+    // コレクション作成時の設定を反映（ディスク配置のうちベクトルは VectorsConfig 側で設定）
+    pub fn apply(&self, mut builder: CreateCollectionBuilder) -> CreateCollectionBuilder {
+        if let Some(hnsw) = self.hnsw_config() {
+            builder = builder.hnsw_config(hnsw);
+        }
+        if let Some(optimizers) = self.optimizers_config() {
+            builder = builder.optimizers_config(optimizers);
+        }
+        if let Some(on_disk) = self.on_disk_payload {
+            builder = builder.on_disk_payload(on_disk);
+        }
+
+        let quantization: Option<quantization_config::Quantization> = match &self.quantization {
+            None | Some(Quantization::Disabled) => None,
+            Some(Quantization::Scalar {
+                quantile,
+                always_ram,
+            }) => Some(scalar(*quantile, *always_ram).into()),
+            Some(Quantization::Product {
+                compression,
+                always_ram,
+            }) => Some(product(*compression, *always_ram).into()),
+            Some(Quantization::Binary { always_ram }) => {
+                Some(BinaryQuantizationBuilder::new(*always_ram).into())
+            }
+        };
+        if let Some(quantization) = quantization {
+            builder = builder.quantization_config(quantization);
+        }
+
+        builder
+    }
+}
+
+fn scalar(quantile: Option<f32>, always_ram: bool) -> ScalarQuantizationBuilder {
+    let builder = ScalarQuantizationBuilder::default().always_ram(always_ram);
+    match quantile {
+        Some(quantile) => builder.quantile(quantile),
+        None => builder,
+    }
+}
+
+fn product(compression: Compression, always_ram: bool) -> ProductQuantizationBuilder {
+    ProductQuantizationBuilder::new(compression.ratio().into()).always_ram(always_ram)
+}
+
+// 既存コレクションに現在の設定を反映（未指定の項目は変更しない）
+pub async fn update(
+    client: &Qdrant,
+    collection_name: &str,
+    tuning: &TuningConfig,
+    vectors: &VectorsConfig,
+) -> Result<()> {
+    let mut request = UpdateCollectionBuilder::new(collection_name);
+
+    if let Some(hnsw) = tuning.hnsw_config() {
+        request = request.hnsw_config(hnsw);
+    }
+    if let Some(optimizers) = tuning.optimizers_config() {
+        request = request.optimizers_config(optimizers);
+    }
+    if let Some(on_disk) = tuning.on_disk_payload {
+        request = request.params(CollectionParamsDiffBuilder::default().on_disk_payload(on_disk));
+    }
+    if let Some(on_disk) = tuning.on_disk_vectors {
+        let params = VectorParamsDiffBuilder::default().on_disk(on_disk).build();
+        let config = if vectors.is_named() {
+            vectors_config_diff::Config::ParamsMap(VectorParamsDiffMap {
+                map: vectors
+                    .models
+                    .iter()
+                    .map(|model| (model.name().to_string(), params))
+                    .collect(),
+            })
+        } else {
+            vectors_config_diff::Config::Params(params)
+        };
+        request = request.vectors_config(config);
+    }
+
+    let quantization: Option<quantization_config_diff::Quantization> = match &tuning.quantization {
+        None => None,
+        Some(Quantization::Disabled) => Some(Disabled {}.into()),
+        Some(Quantization::Scalar {
+            quantile,
+            always_ram,
+        }) => Some(scalar(*quantile, *always_ram).into()),
+        Some(Quantization::Product {
+            compression,
+            always_ram,
+        }) => Some(product(*compression, *always_ram).into()),
+        Some(Quantization::Binary { always_ram }) => {
+            Some(BinaryQuantizationBuilder::new(*always_ram).into())
+        }
+    };
+    if let Some(quantization) = quantization {
+        request = request.quantization_config(quantization);
+    }
+
+    client
+        .update_collection(request)
+        .await
+        .with_context(|| format!("Failed to update collection: {}", collection_name))?;
+    Ok(())
+}
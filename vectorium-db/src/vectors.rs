@@ -0,0 +1,143 @@
+use anyhow::{Context, Result, bail};
+use qdrant_client::qdrant::{Distance, VectorParamsBuilder, Vectors, VectorsConfigBuilder};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use vectorium_common::{EmbeddingModel, EmbeddingPool, EmbeddingPoolConfig};
+
+use crate::collections::VECTOR_DIMENSION;
+use crate::config::Config;
+
+// コレクションのベクトル構成
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct VectorsConfig {
+    // 名前付きベクトルとして格納するモデル（空の場合は既定モデルの無名ベクトル1つ）
+    pub models: Vec<EmbeddingModel>,
+}
+
+impl VectorsConfig {
+    pub fn is_named(&self) -> bool {
+        !self.models.is_empty()
+    }
+
+    // コレクションに定義するモデル
+    pub fn models(&self) -> Vec<EmbeddingModel> {
+        if self.is_named() {
+            self.models.clone()
+        } else {
+            vec![EmbeddingModel::default()]
+        }
+    }
+
+    // インジェストで埋め込むモデル（未指定なら定義済みの全モデル）
+    pub fn select(&self, names: &[String]) -> Result<Vec<EmbeddingModel>> {
+        if names.is_empty() {
+            return Ok(self.models());
+        }
+        if !self.is_named() {
+            bail!("Selecting models requires [vectors] models in the config");
+        }
+
+        names
+            .iter()
+            .map(|name| {
+                EmbeddingModel::from_name(name)
+                    .filter(|model| self.models.contains(model))
+                    .with_context(|| format!("Model {} is not listed in [vectors] models", name))
+            })
+            .collect()
+    }
+
+    // コレクション作成時のベクトル定義（on_disk はベクトルをディスクに置くか）
+    pub fn collection_config(&self, on_disk: Option<bool>) -> qdrant_client::qdrant::VectorsConfig {
+        let params = |dimension| {
+            let params = VectorParamsBuilder::new(dimension, Distance::Cosine);
+            match on_disk {
+                Some(on_disk) => params.on_disk(on_disk),
+                None => params,
+            }
+        };
+
+        if !self.is_named() {
+            return params(VECTOR_DIMENSION).into();
+        }
+
+        let mut builder = VectorsConfigBuilder::default();
+        for model in &self.models {
+            builder.add_named_vector_params(model.name(), params(model.dimension()));
+        }
+        builder.into()
+    }
+}
+
+// 選択したモデルごとの埋め込みワーカープール
+pub struct Embedders {
+    named: bool,
+    pools: Vec<(EmbeddingModel, EmbeddingPool)>,
+}
+
+impl Embedders {
+    // 各モデルのインスタンスを読み込む（ブロッキング）
+    pub fn start(
+        config: &VectorsConfig,
+        models: &[EmbeddingModel],
+        pool_config: &EmbeddingPoolConfig,
+    ) -> Result<Self> {
+        let pools = models
+            .iter()
+            .map(|&model| {
+                let pool = EmbeddingPool::new(model.model_type(), pool_config)
+                    .with_context(|| format!("Failed to start {}", model.name()))?;
+                Ok((model, pool))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            named: config.is_named(),
+            pools,
+        })
+    }
+
+    // モデル1つあたりのインスタンス数
+    pub fn instances(&self) -> usize {
+        self.pools
+            .iter()
+            .map(|(_, pool)| pool.instances())
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn models(&self) -> Vec<EmbeddingModel> {
+        self.pools.iter().map(|&(model, _)| model).collect()
+    }
+
+    // 全モデルで埋め込み、ポイントごとのベクトルを返す
+    pub async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vectors>> {
+        if !self.named {
+            let (_, pool) = self.pools.first().context("No embedding model selected")?;
+            let embeddings = pool.embed(texts).await?;
+            return Ok(embeddings.into_iter().map(Vectors::from).collect());
+        }
+
+        let mut named = vec![HashMap::new(); texts.len()];
+        for (model, pool) in &self.pools {
+            let embeddings = pool.embed(texts.clone()).await?;
+            for (vectors, embedding) in named.iter_mut().zip(embeddings) {
+                vectors.insert(model.name().to_string(), embedding);
+            }
+        }
+
+        Ok(named.into_iter().map(Vectors::from).collect())
+    }
+}
+
+// 選択したモデルごとにインスタンスを読み込んだワーカープールを起動
+pub async fn start_embedders(config: &Config, models: Vec<EmbeddingModel>) -> Result<Embedders> {
+    let config = config.clone();
+    tokio::task::spawn_blocking(move || {
+        Embedders::start(&config.vectors, &models, &config.embedding)
+    })
+    .await
+    .context("Embedding pool startup panicked")?
+}